@@ -0,0 +1,196 @@
+use std::time::Duration;
+
+use chromiumoxide::{Element, Page};
+use chromiumoxide::cdp::js_protocol::runtime::RemoteObjectType;
+use tokio::time::Instant;
+
+use super::error::{ErrorCode, ScrapeError};
+use super::network::NavigationRecorder;
+use super::schema::{Action, ActionKind, ActionResult, ActionStatus, WaitCondition};
+use super::wait;
+
+/// Run `actions` in order. Each step gets `step_timeout` (cut short by `deadline`).
+///
+/// A failing step is recorded and, unless it is optional, the remaining steps are skipped.
+pub async fn run(
+    page: &Page,
+    recorder: &NavigationRecorder,
+    actions: &[Action],
+    step_timeout: Duration,
+    deadline: Instant,
+) -> Vec<ActionResult> {
+    let mut results = skipped(actions);
+    let mut stopped = false;
+    for (action, result) in actions.iter().zip(results.iter_mut()) {
+        if !stopped {
+            let started = Instant::now();
+            let limit = step_timeout.min(deadline.saturating_duration_since(started));
+            let outcome = match tokio::time::timeout(limit, step(page, recorder, &action.kind, limit)).await {
+                Ok(outcome) => outcome,
+                Err(_) => Err(ScrapeError::new(ErrorCode::WaitTimeout, format!("timed out after {}ms", limit.as_millis()))),
+            };
+            result.elapsed_ms = started.elapsed().as_millis() as u64;
+            match outcome {
+                Ok(value) => {
+                    result.status = ActionStatus::Ok;
+                    result.value = value;
+                }
+                Err(e) => {
+                    result.status = ActionStatus::Failed;
+                    result.error = Some(format!("{}: {}", action.kind, e));
+                    result.code = Some(e.code);
+                    stopped = !action.optional;
+                }
+            }
+        }
+    }
+    results
+}
+
+/// Every step marked as not run
+pub fn skipped(actions: &[Action]) -> Vec<ActionResult> {
+    actions
+        .iter()
+        .enumerate()
+        .map(|(index, action)| ActionResult {
+            index,
+            action: action.kind.name().to_string(),
+            status: ActionStatus::Skipped,
+            error: None,
+            code: None,
+            value: None,
+            elapsed_ms: 0,
+        })
+        .collect()
+}
+
+/// The first failure of a required step, as the error of the whole request
+pub fn failure(actions: &[Action], results: &[ActionResult]) -> Option<ScrapeError> {
+    results
+        .iter()
+        .find(|r| r.status == ActionStatus::Failed && !actions[r.index].optional)
+        .map(|r| {
+            let reason = r.error.as_deref().unwrap_or("failed");
+            ScrapeError::new(ErrorCode::ActionFailed, format!("action #{} failed: {}", r.index, reason))
+        })
+}
+
+async fn step(page: &Page, recorder: &NavigationRecorder, kind: &ActionKind, limit: Duration) -> Result<Option<serde_json::Value>, ScrapeError> {
+    match kind {
+        ActionKind::Click { selector } => {
+            element(page, recorder, selector, limit).await?.click().await?;
+        }
+        ActionKind::Type { selector, text, clear } => {
+            let element = element(page, recorder, selector, limit).await?;
+            element.focus().await?;
+            if *clear {
+                element.call_js_fn(
+                    "function() { if ('value' in this) { this.value = ''; this.dispatchEvent(new Event('input', { bubbles: true })); } }",
+                    false,
+                ).await?;
+            }
+            element.type_str(text).await?;
+        }
+        ActionKind::Press { key, selector } => {
+            let element = match selector {
+                Some(selector) => {
+                    let element = element(page, recorder, selector, limit).await?;
+                    element.focus().await?;
+                    element
+                }
+                // key events go to whatever has focus; the root only serves as a handle
+                None => page.find_element("html").await?,
+            };
+            element.press_key(key).await?;
+        }
+        ActionKind::Hover { selector } => {
+            element(page, recorder, selector, limit).await?.hover().await?;
+        }
+        ActionKind::Select { selector, value } => {
+            let element = element(page, recorder, selector, limit).await?;
+            let value = serde_json::to_string(value).unwrap_or_default();
+            let script = format!(
+                "function() {{ const v = {}; \
+                 if (!this.options) return 'not a <select> element'; \
+                 if (![...this.options].some((o) => o.value === v)) return 'no option with value ' + JSON.stringify(v); \
+                 this.value = v; \
+                 this.dispatchEvent(new Event('input', {{ bubbles: true }})); \
+                 this.dispatchEvent(new Event('change', {{ bubbles: true }})); \
+                 return null; }}",
+                value,
+            );
+            let returned = element.call_js_fn(script, false).await?;
+            if returned.result.r#type == RemoteObjectType::String {
+                let message = returned.result.value.and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default();
+                return Err(ScrapeError::new(ErrorCode::ActionFailed, message));
+            }
+        }
+        ActionKind::Scroll { selector: Some(selector), .. } => {
+            element(page, recorder, selector, limit).await?.scroll_into_view().await?;
+        }
+        ActionKind::Scroll { selector: None, y } => {
+            let script = match y {
+                Some(y) => format!("window.scrollBy(0, {})", y),
+                None => "window.scrollTo(0, (document.scrollingElement || document.documentElement).scrollHeight)".to_string(),
+            };
+            page.evaluate_expression(script).await?;
+        }
+        ActionKind::Wait { ms, until } => {
+            if let Some(ms) = ms {
+                tokio::time::sleep(Duration::from_millis(*ms).min(limit)).await;
+            }
+            if let Some(condition) = until {
+                wait::wait_until(page, recorder, std::slice::from_ref(condition), limit).await?;
+            }
+        }
+        ActionKind::Eval { expression } => {
+            let result = page
+                .evaluate_expression(expression.as_str())
+                .await
+                .map_err(|e| ScrapeError::new(ErrorCode::ActionFailed, e.to_string()))?;
+            return Ok(result.value().cloned());
+        }
+    }
+    Ok(None)
+}
+
+/// Wait for `selector` to match and return the element
+async fn element(page: &Page, recorder: &NavigationRecorder, selector: &str, limit: Duration) -> Result<Element, ScrapeError> {
+    let condition = WaitCondition::Selector { selector: selector.to_string() };
+    wait::wait_until(page, recorder, std::slice::from_ref(&condition), limit).await?;
+    page.find_element(selector)
+        .await
+        .map_err(|e| ScrapeError::new(ErrorCode::SelectorNotFound, format!("selector '{}' not found: {}", selector, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn required_failure_is_reported() {
+        let actions: Vec<Action> = serde_json::from_str(
+            r##"[{"type": "click", "selector": "#accept", "optional": true}, {"type": "type", "selector": "#q", "text": "rust"}, {"type": "press", "key": "Enter"}]"##,
+        )
+        .unwrap();
+        assert_eq!(actions[1].kind, ActionKind::Type { selector: "#q".to_string(), text: "rust".to_string(), clear: false });
+
+        let result = |index: usize, status: ActionStatus| ActionResult {
+            index,
+            action: actions[index].kind.name().to_string(),
+            status,
+            error: (status == ActionStatus::Failed).then(|| "boom".to_string()),
+            code: None,
+            value: None,
+            elapsed_ms: 0,
+        };
+        // an optional step failing does not fail the request
+        let results = vec![result(0, ActionStatus::Failed), result(1, ActionStatus::Ok), result(2, ActionStatus::Ok)];
+        assert!(failure(&actions, &results).is_none());
+
+        let results = vec![result(0, ActionStatus::Ok), result(1, ActionStatus::Failed), result(2, ActionStatus::Skipped)];
+        let e = failure(&actions, &results).unwrap();
+        assert_eq!(e.code, ErrorCode::ActionFailed);
+        assert_eq!(e.message, "action #1 failed: boom");
+    }
+}
@@ -0,0 +1,229 @@
+use super::error::{ErrorCode, ScrapeError};
+use super::schema::{CaptureArchive, CaptureManifest, ElementCaptures};
+
+/// Name of the metadata entry in a ZIP response
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+/// 1980-01-01, the earliest DOS date
+const DOS_DATE: u16 = 0x0021;
+/// file names are UTF-8
+const UTF8_NAMES: u16 = 0x0800;
+
+/// Pack captures for the response: the body and its Content-Type.
+pub fn pack(captures: &ElementCaptures, archive: CaptureArchive) -> (Vec<u8>, String) {
+    let manifest = serde_json::to_vec_pretty(&captures.manifest).unwrap_or_default();
+    match archive {
+        CaptureArchive::Zip => {
+            let mut entries = vec![(MANIFEST_FILE, manifest.as_slice())];
+            for (info, image) in captures.manifest.elements.iter().zip(&captures.images) {
+                entries.push((info.file.as_str(), image.as_slice()));
+            }
+            (zip(&entries), "application/zip".to_string())
+        }
+        CaptureArchive::Multipart => {
+            let boundary = format!("capture-{}", uuid::Uuid::new_v4().simple());
+            let content_type = captures.manifest.format.content_type();
+            let mut body = Vec::new();
+            let mut part = |headers: String, data: &[u8]| {
+                body.extend_from_slice(format!("--{}\r\n{}\r\n", boundary, headers).as_bytes());
+                body.extend_from_slice(data);
+                body.extend_from_slice(b"\r\n");
+            };
+            part(
+                format!("Content-Type: application/json\r\nContent-Disposition: inline; name=\"manifest\"; filename=\"{}\"\r\n", MANIFEST_FILE),
+                &manifest,
+            );
+            for (info, image) in captures.manifest.elements.iter().zip(&captures.images) {
+                part(
+                    format!(
+                        "Content-Type: {}\r\nContent-Disposition: attachment; name=\"element-{}\"; filename=\"{}\"\r\nContent-Length: {}\r\n",
+                        content_type, info.index, info.file, image.len(),
+                    ),
+                    image,
+                );
+            }
+            body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+            (body, format!("multipart/mixed; boundary={}", boundary))
+        }
+    }
+}
+
+/// Read a ZIP produced by `pack` back into captures.
+pub fn unpack_zip(data: &[u8]) -> Result<ElementCaptures, ScrapeError> {
+    let invalid = |e: String| ScrapeError::new(ErrorCode::Internal, format!("Invalid capture archive: {}", e));
+    let mut files = unzip(data).map_err(invalid)?;
+    let position = files.iter().position(|(name, _)| name == MANIFEST_FILE).ok_or_else(|| invalid(format!("no {}", MANIFEST_FILE)))?;
+    let (_, manifest) = files.remove(position);
+    let manifest: CaptureManifest = serde_json::from_slice(&manifest).map_err(|e| invalid(e.to_string()))?;
+    let mut images = Vec::with_capacity(manifest.elements.len());
+    for info in &manifest.elements {
+        let (_, image) = files.iter().find(|(name, _)| *name == info.file).ok_or_else(|| invalid(format!("no {}", info.file)))?;
+        images.push(image.clone());
+    }
+    Ok(ElementCaptures { manifest, images })
+}
+
+/// Uncompressed ZIP: images are compressed already.
+fn zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+    for (name, data) in entries {
+        let offset = out.len() as u32;
+        let crc = crc32(data);
+        let size = data.len() as u32;
+
+        put32(&mut out, LOCAL_HEADER);
+        put16(&mut out, 20); // version needed
+        put16(&mut out, UTF8_NAMES);
+        put16(&mut out, 0); // stored
+        put16(&mut out, 0); // time
+        put16(&mut out, DOS_DATE);
+        put32(&mut out, crc);
+        put32(&mut out, size);
+        put32(&mut out, size);
+        put16(&mut out, name.len() as u16);
+        put16(&mut out, 0); // extra
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(data);
+
+        put32(&mut central, CENTRAL_HEADER);
+        put16(&mut central, 20); // version made by
+        put16(&mut central, 20); // version needed
+        put16(&mut central, UTF8_NAMES);
+        put16(&mut central, 0);
+        put16(&mut central, 0);
+        put16(&mut central, DOS_DATE);
+        put32(&mut central, crc);
+        put32(&mut central, size);
+        put32(&mut central, size);
+        put16(&mut central, name.len() as u16);
+        put16(&mut central, 0); // extra
+        put16(&mut central, 0); // comment
+        put16(&mut central, 0); // disk
+        put16(&mut central, 0); // internal attributes
+        put32(&mut central, 0); // external attributes
+        put32(&mut central, offset);
+        central.extend_from_slice(name.as_bytes());
+    }
+    let central_offset = out.len() as u32;
+    out.extend_from_slice(&central);
+    put32(&mut out, END_OF_CENTRAL_DIRECTORY);
+    put16(&mut out, 0);
+    put16(&mut out, 0);
+    put16(&mut out, entries.len() as u16);
+    put16(&mut out, entries.len() as u16);
+    put32(&mut out, central.len() as u32);
+    put32(&mut out, central_offset);
+    put16(&mut out, 0); // comment
+    out
+}
+
+/// Read the entries of an uncompressed ZIP (local headers in order, sizes in the headers).
+fn unzip(data: &[u8]) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut files = Vec::new();
+    let mut at = 0;
+    while read32(data, at)? == LOCAL_HEADER {
+        let flags = read16(data, at + 6)?;
+        let method = read16(data, at + 8)?;
+        let crc = read32(data, at + 14)?;
+        let size = read32(data, at + 18)? as usize;
+        let name_len = read16(data, at + 26)? as usize;
+        let extra_len = read16(data, at + 28)? as usize;
+        if method != 0 || flags & 0x0008 != 0 {
+            return Err("only stored entries with sizes in the header are supported".to_string());
+        }
+        let start = at + 30;
+        let name = data.get(start..start + name_len).ok_or("truncated name")?;
+        let body = start + name_len + extra_len;
+        let content = data.get(body..body + size).ok_or("truncated entry")?;
+        if crc32(content) != crc {
+            return Err(format!("CRC mismatch in {}", String::from_utf8_lossy(name)));
+        }
+        files.push((String::from_utf8_lossy(name).into_owned(), content.to_vec()));
+        at = body + size;
+    }
+    Ok(files)
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (i, entry) in table.iter_mut().enumerate() {
+        let mut c = i as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 { 0xEDB8_8320 ^ (c >> 1) } else { c >> 1 };
+        }
+        *entry = c;
+    }
+    !data.iter().fold(!0u32, |crc, &b| table[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8))
+}
+
+fn put16(out: &mut Vec<u8>, v: u16) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put32(out: &mut Vec<u8>, v: u32) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+fn read16(data: &[u8], at: usize) -> Result<u16, String> {
+    data.get(at..at + 2).map(|b| u16::from_le_bytes([b[0], b[1]])).ok_or_else(|| "truncated header".to_string())
+}
+
+fn read32(data: &[u8], at: usize) -> Result<u32, String> {
+    data.get(at..at + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]])).ok_or_else(|| "truncated header".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{ClipRect, ElementCaptureInfo, ImageFormat};
+
+    fn captures() -> ElementCaptures {
+        let rect = ClipRect { x: 0.0, y: 10.0, width: 100.0, height: 20.0 };
+        let info = |index: usize| ElementCaptureInfo {
+            index,
+            selector: ".card".to_string(),
+            match_index: index,
+            file: format!("element-{}.png", index),
+            bounding_box: rect,
+            clip: rect,
+        };
+        ElementCaptures {
+            manifest: CaptureManifest { format: ImageFormat::Png, elements: vec![info(0), info(1)], missing: vec!["#gone".to_string()], ..Default::default() },
+            images: vec![b"\x89PNG first".to_vec(), b"\x89PNG second".to_vec()],
+        }
+    }
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn zip_round_trip() {
+        let captures = captures();
+        let (body, content_type) = pack(&captures, CaptureArchive::Zip);
+        assert_eq!(content_type, "application/zip");
+        assert_eq!(&body[..4], b"PK\x03\x04");
+        assert_eq!(unpack_zip(&body).unwrap(), captures);
+
+        let mut corrupt = body.clone();
+        let at = corrupt.windows(5).position(|w| w == b"first").unwrap();
+        corrupt[at] = b'F';
+        assert!(unpack_zip(&corrupt).is_err());
+    }
+
+    #[test]
+    fn multipart_parts() {
+        let (body, content_type) = pack(&captures(), CaptureArchive::Multipart);
+        let boundary = content_type.strip_prefix("multipart/mixed; boundary=").unwrap();
+        let body = String::from_utf8_lossy(&body);
+        assert_eq!(body.matches(&format!("--{}\r\n", boundary)).count(), 3);
+        assert!(body.ends_with(&format!("--{}--\r\n", boundary)));
+        assert!(body.contains("filename=\"element-1.png\"\r\nContent-Length: 11\r\n\r\n\u{fffd}PNG second\r\n"));
+    }
+}
@@ -1,25 +1,466 @@
+use std::ops::Deref;
 use std::time::Duration;
-use std::{collections::HashMap, error::Error};
-use std::sync::Arc;
+use std::error::Error;
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, AtomicU64, Ordering}};
 
 
-use chromiumoxide::{Browser, BrowserConfig, Page, browser::HeadlessMode, cdp::browser_protocol::{emulation::{SetGeolocationOverrideParamsBuilder, SetTimezoneOverrideParamsBuilder}, page::{CaptureScreenshotFormat, ViewportBuilder}, target::CreateTargetParamsBuilder}, handler::viewport::Viewport, page::ScreenshotParamsBuilder};
-use tokio::sync::RwLock;
+use chromiumoxide::{Browser, BrowserConfig, Element, Handler, Page, browser::HeadlessMode, cdp::browser_protocol::{browser::BrowserContextId, emulation::{SetDeviceMetricsOverrideParams, SetGeolocationOverrideParamsBuilder, SetLocaleOverrideParams, SetTimezoneOverrideParams, SetTouchEmulationEnabledParams}, network::{CookieParam, CookieSameSite, SetUserAgentOverrideParams, TimeSinceEpoch}, page::{AddScriptToEvaluateOnNewDocumentParams, CaptureScreenshotFormat, PrintToPdfParams, RemoveScriptToEvaluateOnNewDocumentParams, ScriptIdentifier, ViewportBuilder}, storage::{GetCookiesParams, SetCookiesParams}, target::{CreateBrowserContextParams, CreateTargetParamsBuilder}}, error::CdpError, handler::viewport::Viewport, page::ScreenshotParamsBuilder};
+use tokio::sync::{OwnedSemaphorePermit, RwLock, Semaphore, watch};
+use tokio::time::Instant;
 use futures::StreamExt;
-use scraper::{Html, Selector};
+use serde::Deserialize;
 
-use super::schema::ScrapeResults;
+use super::device::{self, ResolvedEmulation};
+use super::error::{ErrorCode, ScrapeError};
+use super::fetch::{FetchedPage, Fetcher};
+use super::intercept::{BlockList, RequestInterceptor};
+use super::network::{DocumentError, DocumentResponse, NavigationRecorder};
+use super::proxy::{ProxyConfig, ProxyPool, ProxyPoolConfig};
+use super::robots::RobotsCache;
+use super::schema::{Action, ActionResult, AutoScroll, CaptureOptions, ClipRect, DEFAULT_IMAGE_QUALITY, ElementCaptureInfo, ElementCaptures, ElementRef, Emulation, EngineStats, Geolocation, ImageFormat, PdfOptions, RenderMode, RequestOptions, ScrapeResults, ScrapeSpec, ScrollReport, Session, SessionCookie, SpecResults, WaitCondition};
+use super::session::{OriginStorage, SessionStore};
 
-use super::utils;
+use super::{actions, extract, scroll, spec, utils, wait};
+
+/// Engine configuration
+#[derive(Debug, Clone)]
+pub struct EngineConfig {
+    pub pool: PoolConfig,
+    pub timeouts: TimeoutConfig,
+    /// Emulation used for whatever a request does not set
+    pub emulation: Emulation,
+    /// Outbound proxies (none: connect directly)
+    pub proxies: ProxyPoolConfig,
+    /// How often the supervisor checks that the Chromium process is still running
+    pub health_check_interval: Duration,
+    /// Upper bound of the exponential backoff between failed relaunch attempts
+    pub max_restart_backoff: Duration,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        EngineConfig {
+            pool: PoolConfig::default(),
+            timeouts: TimeoutConfig::default(),
+            emulation: Emulation {
+                device: Some("desktop".to_string()),
+                timezone: Some("Asia/Tokyo".to_string()),
+                // 大阪日本橋 err 100m
+                geolocation: Some(Geolocation { latitude: 34.6676, longitude: 135.5063, accuracy: 100.0 }),
+                ..Default::default()
+            },
+            proxies: ProxyPoolConfig::default(),
+            health_check_interval: Duration::from_secs(5),
+            max_restart_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Request timeout defaults (used when a request sets none) and upper bounds
+#[derive(Debug, Clone)]
+pub struct TimeoutConfig {
+    pub default_total: Duration,
+    pub max_total: Duration,
+    pub default_navigation: Duration,
+    pub max_navigation: Duration,
+    pub default_selector: Duration,
+    pub max_selector: Duration,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        TimeoutConfig {
+            default_total: Duration::from_secs(60),
+            max_total: Duration::from_secs(300),
+            default_navigation: Duration::from_secs(30),
+            max_navigation: Duration::from_secs(120),
+            default_selector: Duration::from_secs(10),
+            max_selector: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Timeouts in effect for one request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    pub total: Duration,
+    pub navigation: Duration,
+    pub selector: Duration,
+}
+
+impl TimeoutConfig {
+    /// Apply the defaults to the timeouts a request asked for and clamp them to the maxima.
+    pub fn resolve(&self, options: &RequestOptions) -> Timeouts {
+        let pick = |requested: Option<u64>, default: Duration, max: Duration| {
+            requested.map(Duration::from_millis).unwrap_or(default).min(max)
+        };
+        let total = pick(options.timeout_ms, self.default_total, self.max_total);
+        Timeouts {
+            total,
+            navigation: pick(options.navigation_timeout_ms, self.default_navigation, self.max_navigation).min(total),
+            selector: pick(options.selector_timeout_ms, self.default_selector, self.max_selector).min(total),
+        }
+    }
+}
+
+/// Tab pool configuration
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    /// Maximum number of tabs open at the same time
+    pub max_tabs: usize,
+    /// Maximum number of warm tabs kept for reuse
+    pub max_idle: usize,
+    /// How long a request waits for a free tab before failing (`None` waits forever)
+    pub acquire_timeout: Option<Duration>,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            max_tabs: 8,
+            max_idle: 8,
+            acquire_timeout: Some(Duration::from_secs(30)),
+        }
+    }
+}
+
+struct PooledTab {
+    page: Page,
+    context_id: BrowserContextId,
+    /// browser generation the tab was opened in
+    generation: u64,
+    /// proxy server of the tab's browser context
+    proxy: Option<String>,
+}
+
+/// Pool of warm browser tabs.
+///
+/// Every tab lives in its own browser context, so cookies and storage never leak
+/// between concurrent requests, and each context can use its own proxy. Tabs are never
+/// reused: a released tab is closed with its context and replaced by a fresh one.
+///
+/// At most `max_tabs` tabs are leased at once; further requests queue on a semaphore
+/// until a tab is released or `acquire_timeout` expires.
+pub struct PagePool {
+    browser: Arc<RwLock<Browser>>,
+    config: PoolConfig,
+    permits: Arc<Semaphore>,
+    idle: Mutex<Vec<PooledTab>>,
+    generation: AtomicU64,
+}
+
+impl PagePool {
+    pub fn new(browser: Arc<RwLock<Browser>>, config: PoolConfig) -> Arc<Self> {
+        Arc::new(PagePool {
+            browser,
+            permits: Arc::new(Semaphore::new(config.max_tabs)),
+            config,
+            idle: Mutex::new(Vec::new()),
+            generation: AtomicU64::new(0),
+        })
+    }
+
+    /// Lease a tab whose browser context uses `proxy` (a proxy server as Chromium takes it),
+    /// reusing a warm one when available.
+    pub async fn acquire(self: &Arc<Self>, proxy: Option<&str>) -> Result<PageLease, Box<dyn Error>> {
+        let permit = match self.config.acquire_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, self.permits.clone().acquire_owned())
+                .await
+                .map_err(|_| ScrapeError::new(ErrorCode::Busy, "page pool exhausted: timed out waiting for a free tab"))??,
+            None => self.permits.clone().acquire_owned().await?,
+        };
+        let (warm, evicted) = {
+            let mut idle = self.idle.lock().unwrap();
+            match idle.iter().rposition(|tab| tab.proxy.as_deref() == proxy) {
+                Some(i) => (Some(idle.remove(i)), None),
+                // make room for the new tab, or warm tabs of other proxies would fill the pool for good
+                None if idle.len() >= self.config.max_idle && !idle.is_empty() => (None, Some(idle.remove(0))),
+                None => (None, None),
+            }
+        };
+        if let Some(tab) = evicted {
+            self.discard_tab(tab).await;
+        }
+        let tab = match warm {
+            Some(tab) => tab,
+            None => self.open_tab(proxy).await?,
+        };
+        Ok(PageLease {
+            pool: self.clone(),
+            tab: Some(tab),
+            permit: Some(permit),
+        })
+    }
+
+    /// Number of tabs currently leased
+    pub fn in_use(&self) -> usize {
+        self.config.max_tabs - self.permits.available_permits()
+    }
+
+    /// Number of warm tabs waiting for reuse
+    pub fn idle(&self) -> usize {
+        self.idle.lock().unwrap().len()
+    }
+
+    /// Browser generation, incremented every time Chromium is relaunched
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Forget every warm tab after the browser was replaced.
+    /// Tabs still leased from the old browser are dropped when they come back.
+    fn reset(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.idle.lock().unwrap().clear();
+    }
+
+    /// Close every warm tab.
+    pub async fn clear(&self) {
+        let tabs = std::mem::take(&mut *self.idle.lock().unwrap());
+        for tab in tabs {
+            self.discard_tab(tab).await;
+        }
+    }
+
+    async fn open_tab(&self, proxy: Option<&str>) -> Result<PooledTab, Box<dyn Error>> {
+        let generation = self.generation();
+        let b = self.browser.read().await;
+        let mut context = CreateBrowserContextParams::builder();
+        if let Some(proxy) = proxy {
+            context = context.proxy_server(proxy);
+        }
+        let context_id = b.create_browser_context(context.build()).await?;
+        let target_params = CreateTargetParamsBuilder::default()
+            .url("about:blank")
+            .browser_context_id(context_id.clone())
+            .build()?;
+        let page = match b.new_page(target_params).await {
+            Ok(page) => page,
+            Err(e) => {
+                let _ = b.dispose_browser_context(context_id).await;
+                return Err(e.into());
+            }
+        };
+        let tab = PooledTab { page, context_id, generation, proxy: proxy.map(str::to_string) };
+        // stringify the error before awaiting again: `Box<dyn Error>` is not `Send`
+        let setup = Self::setup_page(&tab.page).await.map_err(|e| e.to_string());
+        if let Err(e) = setup {
+            drop(b);
+            self.discard_tab(tab).await;
+            return Err(e.into());
+        }
+        Ok(tab)
+    }
+
+    /// Device, locale and geolocation are set per request by `Engine::emulate`.
+    async fn setup_page(page: &Page) -> Result<(), Box<dyn Error>> {
+        page.enable_stealth_mode_with_agent(Engine::UA).await?;
+        Ok(())
+    }
+
+    async fn discard_tab(&self, tab: PooledTab) {
+        if tab.generation != self.generation() {
+            // the browser it belonged to is gone
+            return;
+        }
+        let _ = tab.page.close().await;
+        let b = self.browser.read().await;
+        let _ = b.dispose_browser_context(tab.context_id).await;
+    }
+}
+
+/// A tab leased from the `PagePool`.
+///
+/// Call `release` to hand the slot back to the pool with a fresh tab. A lease that is
+/// dropped without being released (e.g. on an early `?` return) closes its tab.
+pub struct PageLease {
+    pool: Arc<PagePool>,
+    tab: Option<PooledTab>,
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl PageLease {
+    /// Browser context the tab lives in
+    pub fn context_id(&self) -> &BrowserContextId {
+        &self.tab.as_ref().expect("page lease already released").context_id
+    }
+
+    /// Close the tab and its browser context, and warm up a replacement in a fresh context,
+    /// so nothing the request left behind (cookies, storage or cache of any origin) reaches the next lease.
+    pub async fn release(mut self) {
+        if let Some(tab) = self.tab.take() {
+            let generation = tab.generation;
+            let proxy = tab.proxy.clone();
+            self.pool.discard_tab(tab).await;
+            let reusable = generation == self.pool.generation()
+                && self.pool.idle.lock().unwrap().len() < self.pool.config.max_idle;
+            if reusable {
+                // stringify the error before awaiting again: `Box<dyn Error>` is not `Send`
+                let fresh = self.pool.open_tab(proxy.as_deref()).await.map_err(|e| e.to_string());
+                if let Ok(tab) = fresh {
+                    self.pool.idle.lock().unwrap().push(tab);
+                }
+            }
+        }
+    }
+}
+
+impl Deref for PageLease {
+    type Target = Page;
+
+    fn deref(&self) -> &Page {
+        &self.tab.as_ref().expect("page lease already released").page
+    }
+}
+
+impl Drop for PageLease {
+    fn drop(&mut self) {
+        if let Some(tab) = self.tab.take() {
+            let pool = self.pool.clone();
+            // keep the permit until the tab is actually closed
+            let permit = self.permit.take();
+            tokio::spawn(async move {
+                pool.discard_tab(tab).await;
+                drop(permit);
+            });
+        }
+    }
+}
+
+struct Health {
+    alive: watch::Sender<bool>,
+    restarts: AtomicU64,
+    shutdown: AtomicBool,
+}
 
 pub struct Engine {
     pub browser: Arc<RwLock<Browser>>,
+    /// supervisor task driving the CDP handler and relaunching Chromium when it dies
     pub handle: tokio::task::JoinHandle<()>,
+    pub pool: Arc<PagePool>,
+    /// static HTML backend used when rendering is disabled
+    pub fetcher: Fetcher,
+    pub robots: RobotsCache,
+    pub timeouts: TimeoutConfig,
+    /// server defaults for the emulation settings
+    pub emulation: Emulation,
+    /// outbound proxies with their health
+    pub proxies: ProxyPool,
+    /// named sessions (cookies and web storage kept between requests)
+    pub sessions: SessionStore,
+    health: Arc<Health>,
+}
+
+/// How often `wait_for` looks for the selector again
+const SELECTOR_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Extra time a partial request gets after its deadline to read the DOM
+const PARTIAL_GRACE: Duration = Duration::from_secs(5);
+
+/// Elements captured at most by one multi-element capture, unless `max_elements` says otherwise
+const DEFAULT_MAX_ELEMENTS: usize = 100;
+
+/// Scroll the element (and the scroll containers around it) into view, let layout settle
+/// for two frames and report its box in page coordinates, as JSON (`null` once detached)
+const ELEMENT_GEOMETRY_SCRIPT: &str = "async function() { \
+    if (!this.isConnected) return 'null'; \
+    this.scrollIntoView({ block: 'center', inline: 'center', behavior: 'instant' }); \
+    for (let i = 0; i < 2; i++) await new Promise((done) => requestAnimationFrame(done)); \
+    const rect = this.getBoundingClientRect(); \
+    const style = getComputedStyle(this); \
+    const root = document.scrollingElement || document.documentElement; \
+    return JSON.stringify({ \
+        x: rect.left + window.scrollX, y: rect.top + window.scrollY, width: rect.width, height: rect.height, \
+        hidden: style.display === 'none' || style.visibility === 'hidden' || this.getClientRects().length === 0, \
+        page_width: Math.max(root.scrollWidth, root.clientWidth), \
+        page_height: Math.max(root.scrollHeight, root.clientHeight) }); }";
+
+/// What `ELEMENT_GEOMETRY_SCRIPT` reports
+#[derive(Debug, Deserialize)]
+struct ElementGeometry {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    hidden: bool,
+    page_width: f64,
+    page_height: f64,
+}
+
+/// Deadlines of one request
+#[derive(Debug, Clone, Copy)]
+struct Budget {
+    deadline: Instant,
+    timeouts: Timeouts,
+    partial: bool,
+}
+
+impl Budget {
+    fn new(timeouts: Timeouts, partial: bool) -> Self {
+        Budget { deadline: Instant::now() + timeouts.total, timeouts, partial }
+    }
+
+    /// `step`, cut short by the overall deadline
+    fn limit(&self, step: Duration) -> Duration {
+        step.min(self.deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Run the whole request; past the deadline (plus a grace period for partial requests)
+    /// it is dropped, which closes its tab.
+    async fn run<T>(&self, request: impl Future<Output = Result<T, ScrapeError>>) -> Result<T, ScrapeError> {
+        let hard = if self.partial { self.deadline + PARTIAL_GRACE } else { self.deadline };
+        tokio::time::timeout_at(hard, request).await.map_err(|_| {
+            ScrapeError::new(ErrorCode::NavigationTimeout, format!("request timed out after {}ms", self.timeouts.total.as_millis()))
+        })?
+    }
+}
+
+/// A page navigated to the requested URL
+struct Navigation {
+    page: PageLease,
+    /// keeps counting in-flight requests for `network_idle` waits
+    recorder: NavigationRecorder,
+    /// keeps failing blocked requests and answering proxy challenges while the page is in use
+    _interceptor: Option<RequestInterceptor>,
+    response: DocumentResponse,
+    /// the navigation hit its timeout and the request asked for partial results
+    timed_out: bool,
 }
 
 impl Engine {
     pub async fn new() -> Result<Self, Box<dyn Error>> {
-        let (browser, mut handler) = Browser::launch(
+        Self::with_config(EngineConfig::default()).await
+    }
+
+    pub async fn with_config(config: EngineConfig) -> Result<Self, Box<dyn Error>> {
+        let (browser, handler) = Self::launch().await?;
+        let browser = Arc::new(RwLock::new(browser));
+        let pool = PagePool::new(browser.clone(), config.pool.clone());
+        let timeouts = config.timeouts.clone();
+        let emulation = config.emulation.clone();
+        let proxies = ProxyPool::new(config.proxies.clone());
+        let health = Arc::new(Health {
+            alive: watch::Sender::new(true),
+            restarts: AtomicU64::new(0),
+            shutdown: AtomicBool::new(false),
+        });
+        let handle = tokio::task::spawn(Self::supervise(
+            browser.clone(),
+            pool.clone(),
+            health.clone(),
+            handler,
+            config,
+        ));
+        let fetcher = Fetcher::new(Self::UA)?;
+        let robots = RobotsCache::new(Self::UA)?;
+        let sessions = SessionStore::new();
+        Ok(Engine { browser, handle, pool, fetcher, robots, timeouts, emulation, proxies, sessions, health })
+    }
+    
+    pub(crate) const UA: &str = device::DESKTOP_UA;
+
+    async fn launch() -> Result<(Browser, Handler), Box<dyn Error>> {
+        let launched = Browser::launch(
             BrowserConfig::builder()
                 .viewport(
                     Viewport {
@@ -36,50 +477,476 @@ impl Engine {
                 .headless_mode(HeadlessMode::True)
                 .build()?,
         ).await?;
-        let browser = Arc::new(RwLock::new(browser));
-        let handle = tokio::task::spawn(async move {
-            while let Some(h) = handler.next().await {
-                if h.is_err() {
-                    break;
+        Ok(launched)
+    }
+
+    /// Drive the CDP handler; when the browser dies, relaunch it and swap it in.
+    async fn supervise(
+        browser: Arc<RwLock<Browser>>,
+        pool: Arc<PagePool>,
+        health: Arc<Health>,
+        mut handler: Handler,
+        config: EngineConfig,
+    ) {
+        loop {
+            Self::drive(&browser, &mut handler, config.health_check_interval).await;
+            if health.shutdown.load(Ordering::SeqCst) {
+                return;
+            }
+            health.alive.send_replace(false);
+            log::warn!("browser died, restarting...");
+            // fail the CDP calls still waiting on the dead connection, so the tabs holding
+            // a read guard let go of it before the browser is swapped
+            drop(handler);
+
+            let mut backoff = Duration::from_secs(1);
+            handler = loop {
+                // stringify the error right away: `Box<dyn Error>` is not `Send`
+                let launched = Self::launch().await.map_err(|e| e.to_string());
+                match launched {
+                    Ok((new_browser, new_handler)) => {
+                        let mut old = std::mem::replace(&mut *browser.write().await, new_browser);
+                        let _ = old.kill().await;
+                        pool.reset();
+                        break new_handler;
+                    }
+                    Err(e) => log::error!("browser relaunch failed: {}", e),
                 }
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(config.max_restart_backoff);
+            };
+
+            let restarts = health.restarts.fetch_add(1, Ordering::SeqCst) + 1;
+            health.alive.send_replace(true);
+            log::info!("browser restarted (restarts: {})", restarts);
+        }
+    }
+
+    /// Poll the handler until the connection fails or the Chromium process exits.
+    ///
+    /// The process check runs in its own task so that it never holds up the handler,
+    /// which tabs holding a read guard depend on for their CDP responses.
+    async fn drive(browser: &Arc<RwLock<Browser>>, handler: &mut Handler, interval: Duration) {
+        let mut exited = tokio::spawn(Self::watch_process(browser.clone(), interval));
+        loop {
+            tokio::select! {
+                event = handler.next() => match event {
+                    Some(Ok(_)) => {}
+                    // a malformed message does not mean the connection is gone
+                    Some(Err(CdpError::InvalidMessage(..))) => {}
+                    Some(Err(_)) | None => break,
+                },
+                _ = &mut exited => return,
             }
-        });
-        Ok(Engine { browser, handle })
+        }
+        exited.abort();
+    }
+
+    /// Return once the Chromium process has exited.
+    ///
+    /// Checking the process needs the write lock, so it is only done while no tab holds
+    /// the browser: waiting for the lock would stop new tabs from being acquired. A busy
+    /// browser that dies is noticed by the handler when its connection closes.
+    async fn watch_process(browser: Arc<RwLock<Browser>>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Ok(mut browser) = browser.try_write()
+                && let Ok(Some(_)) = browser.try_wait()
+            {
+                return;
+            }
+        }
     }
-    
-    const UA: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36 (+https://371tti.net)";
 
     pub async fn shutdown(&self) -> Result<(), Box<dyn Error>> {
-        // Abort the background handler task (if still running) and close the browser.
+        // Close pooled tabs, stop the supervisor (and its handler) and close the browser.
+        self.health.shutdown.store(true, Ordering::SeqCst);
+        self.pool.clear().await;
         self.handle.abort();
         let mut b = self.browser.write().await;
         let _ = b.kill().await;
         Ok(())
     }
 
-    async fn new_page(&self, url: &str) -> Result<Page, Box<dyn Error>> {
+    /// Browser health and pool usage
+    pub fn stats(&self) -> EngineStats {
+        EngineStats {
+            alive: *self.health.alive.borrow(),
+            restarts: self.health.restarts.load(Ordering::SeqCst),
+            generation: self.pool.generation(),
+            tabs_in_use: self.pool.in_use(),
+            tabs_idle: self.pool.idle(),
+            proxies: self.proxies.stats(),
+        }
+    }
+
+    /// Run a request, reporting a clear error when the browser was restarted underneath it.
+    async fn guarded<T>(
+        &self,
+        request: impl Future<Output = Result<T, ScrapeError>>,
+    ) -> Result<T, ScrapeError> {
+        let generation = self.pool.generation();
+        let result = request.await;
+        match result {
+            Err(e) if !*self.health.alive.borrow() || self.pool.generation() != generation => {
+                Err(ScrapeError::new(ErrorCode::BrowserRestarted, format!("browser restarted during request: {}", e)))
+            }
+            result => result,
+        }
+    }
+
+    /// Refuse URLs disallowed by robots.txt unless the request overrides it.
+    /// robots.txt is fetched through the request's proxy; fetching it and waiting for
+    /// the crawl delay count against the request budget.
+    async fn check_robots(&self, url: &str, budget: &Budget, options: &RequestOptions, proxy: Option<&ProxyConfig>) -> Result<(), ScrapeError> {
+        if options.ignore_robots {
+            return Ok(());
+        }
+        let checked = budget.run(self.robots.check(&utils::url_decode(url), budget.deadline.into_std(), proxy)).await;
+        // only failures: the page request itself reports the successes
+        if let (Err(_), Some(proxy)) = (&checked, proxy) {
+            self.report_proxy(proxy, &checked);
+        }
+        checked
+    }
+
+    /// Wait until the browser is up again if a restart is in progress.
+    async fn wait_alive(&self) -> Result<(), ScrapeError> {
+        let mut alive = self.health.alive.subscribe();
+        let wait = alive.wait_for(|alive| *alive);
+        let unavailable = || ScrapeError::new(ErrorCode::BrowserUnavailable, "browser unavailable: restart in progress");
+        tokio::time::timeout(Duration::from_secs(60), wait)
+            .await
+            .map_err(|_| unavailable())?
+            .map_err(|_| unavailable())?;
+        Ok(())
+    }
+
+    fn budget(&self, options: &RequestOptions) -> Budget {
+        Budget::new(self.timeouts.resolve(options), options.partial_results)
+    }
+
+    /// Proxy for a request: the request's own (`direct` for none), or one from the pool.
+    fn select_proxy(&self, url: &str, options: &RequestOptions) -> Result<Option<ProxyConfig>, ScrapeError> {
+        match options.proxy.as_deref() {
+            Some("direct") => Ok(None),
+            Some(proxy) => ProxyConfig::parse(proxy).map(Some),
+            None => self.proxies.select(&utils::url_decode(url)),
+        }
+    }
+
+    /// Record in the pool whether `proxy` carried the request.
+    /// Failures that happen before anything is sent through the proxy are not counted.
+    fn report_proxy<T>(&self, proxy: &ProxyConfig, result: &Result<T, ScrapeError>) {
+        let ok = match result {
+            Ok(_) => true,
+            Err(e) => match e.code {
+                ErrorCode::ProxyFailed => false,
+                // the proxy relayed an answer from the site
+                ErrorCode::DnsFailure
+                | ErrorCode::ConnectionFailed
+                | ErrorCode::TlsFailure
+                | ErrorCode::DocumentFailed
+                | ErrorCode::UnsupportedContent
+                | ErrorCode::SelectorNotFound
+                | ErrorCode::WaitTimeout => true,
+                _ => return,
+            },
+        };
+        self.proxies.report(proxy, ok);
+    }
+
+    /// Proxy server to hand to Chromium. It cannot be given credentials, so the
+    /// `RequestInterceptor` of the page answers the proxy's challenges.
+    fn browser_proxy(proxy: &ProxyConfig) -> Result<String, ScrapeError> {
+        if proxy.has_credentials() && proxy.scheme.starts_with("socks") {
+            return Err(ScrapeError::invalid_request(format!(
+                "proxy {}: credentials are not supported for SOCKS5 proxies when rendering",
+                proxy,
+            )));
+        }
+        // Chromium resolves host names through SOCKS5 proxies by itself
+        Ok(proxy.server().replacen("socks5h://", "socks5://", 1))
+    }
+
+    /// Download `url` without the browser, through the request's proxy.
+    /// The navigation timeout bounds the whole redirect chain, not each hop.
+    async fn fetch_static(&self, url: &str, budget: &Budget, proxy: Option<&ProxyConfig>) -> Result<FetchedPage, ScrapeError> {
+        let limit = budget.limit(budget.timeouts.navigation);
+        let fetched = match tokio::time::timeout(limit, self.fetcher.fetch(url, limit, proxy)).await {
+            Ok(fetched) => fetched,
+            Err(_) => Err(ScrapeError::new(
+                ErrorCode::NavigationTimeout,
+                format!("navigation timed out after {}ms", limit.as_millis()),
+            )),
+        };
+        if let Some(proxy) = proxy {
+            self.report_proxy(proxy, &fetched);
+        }
+        fetched
+    }
+
+    /// Open `url` in a pooled tab, applying the request's block rules and proxy, and record the main document response.
+    /// Fails with `DocumentError` when the document itself could not be loaded, and with
+    /// a timeout when the navigation does not finish in time (unless partial results were asked for).
+    async fn navigate(&self, url: &str, budget: &Budget, options: &RequestOptions, proxy: Option<&ProxyConfig>) -> Result<Navigation, ScrapeError> {
+        let navigation = self.open(url, budget, options, proxy).await;
+        if let Some(proxy) = proxy {
+            self.report_proxy(proxy, &navigation);
+        }
+        navigation
+    }
+
+    async fn open(&self, url: &str, budget: &Budget, options: &RequestOptions, proxy: Option<&ProxyConfig>) -> Result<Navigation, ScrapeError> {
         let decoded_url = utils::url_decode(url);
-        let b = self.browser.read().await;
-        b.clear_cookies().await?;
-        let target_params = CreateTargetParamsBuilder::default()
-            .url(&decoded_url)
+        let block_list = BlockList::new(&options.block)?;
+        let emulation = ResolvedEmulation::resolve(&options.emulation.or(&self.emulation))?;
+        let session = options.session.as_deref().map(|id| self.sessions.checkout(id)).transpose()?;
+        let proxy_server = proxy.map(Self::browser_proxy).transpose()?;
+        self.wait_alive().await?;
+        let page = self.pool.acquire(proxy_server.as_deref()).await.map_err(ScrapeError::from)?;
+        Self::emulate(&page, &emulation).await?;
+        let restore_script = match &session {
+            Some(session) => Self::restore_session(&page, session).await?,
+            None => None,
+        };
+        let interceptor = RequestInterceptor::start(&page, block_list, proxy).await.map_err(ScrapeError::from)?;
+        let recorder = NavigationRecorder::start(&page).await.map_err(ScrapeError::from)?;
+        let limit = budget.limit(budget.timeouts.navigation);
+        let navigation = match tokio::time::timeout(limit, page.goto(decoded_url.as_str())).await {
+            Ok(result) => result.map(|_| ()).map_err(ScrapeError::from),
+            Err(_) => Err(ScrapeError::new(
+                ErrorCode::NavigationTimeout,
+                format!("navigation timed out after {}ms", limit.as_millis()),
+            )),
+        };
+        if let Some(script) = restore_script {
+            // later navigations of the request (e.g. after a login form) keep what the site stored
+            let _ = page.execute(RemoveScriptToEvaluateOnNewDocumentParams::new(script)).await;
+        }
+        let response = recorder.document().await;
+        if let Some(reason) = &response.failure {
+            return Err(DocumentError { url: decoded_url, reason: reason.clone() }.into());
+        }
+        let timed_out = match navigation {
+            Err(e) if e.code == ErrorCode::NavigationTimeout && budget.partial => true,
+            navigation => {
+                navigation?;
+                false
+            }
+        };
+        Ok(Navigation { page, recorder, _interceptor: interceptor, response, timed_out })
+    }
+
+    /// Apply device metrics, user agent, locale, timezone and geolocation to a fresh tab.
+    async fn emulate(page: &Page, emulation: &ResolvedEmulation) -> Result<(), ScrapeError> {
+        page.execute(SetDeviceMetricsOverrideParams::new(
+            emulation.width,
+            emulation.height,
+            emulation.device_scale_factor,
+            emulation.mobile,
+        )).await?;
+        page.execute(SetTouchEmulationEnabledParams::new(emulation.touch)).await?;
+        let mut user_agent = SetUserAgentOverrideParams::builder().user_agent(emulation.user_agent.clone());
+        if let Some(accept_language) = &emulation.accept_language {
+            user_agent = user_agent.accept_language(accept_language.clone());
+        }
+        page.set_user_agent(user_agent.build()?).await?;
+        if let Some(locale) = &emulation.locale {
+            page.emulate_locale(SetLocaleOverrideParams { locale: Some(locale.clone()) }).await?;
+        }
+        if let Some(timezone) = &emulation.timezone {
+            page.emulate_timezone(SetTimezoneOverrideParams::new(timezone.clone()))
+                .await
+                .map_err(|e| ScrapeError::invalid_request(format!("invalid timezone '{}': {}", timezone, e)))?;
+        }
+        if let Some(geo) = &emulation.geolocation {
+            page.emulate_geolocation(
+                SetGeolocationOverrideParamsBuilder::default()
+                    .latitude(geo.latitude)
+                    .longitude(geo.longitude)
+                    .accuracy(geo.accuracy)
+                    .build()
+            ).await?;
+        }
+        Ok(())
+    }
+
+    /// Put a session's cookies into the tab's browser context and have its web storage
+    /// written into each document of a matching origin. Returns the storage script to
+    /// remove once the first navigation is done.
+    async fn restore_session(page: &PageLease, session: &Session) -> Result<Option<ScriptIdentifier>, ScrapeError> {
+        if !session.cookies.is_empty() {
+            let cookies = session.cookies.iter().map(cookie_param).collect::<Result<Vec<_>, _>>()?;
+            page.execute(SetCookiesParams { cookies, browser_context_id: Some(page.context_id().clone()) }).await?;
+        }
+        if session.local_storage.is_empty() && session.session_storage.is_empty() {
+            return Ok(None);
+        }
+        // web storage can only be written from a document of its origin
+        let storage = serde_json::json!({ "local": session.local_storage, "session": session.session_storage });
+        let source = format!(
+            "(() => {{ const s = {}; for (const [kind, area] of [['local', 'localStorage'], ['session', 'sessionStorage']]) {{ \
+             const items = s[kind][location.origin]; if (!items) continue; \
+             try {{ for (const [k, v] of Object.entries(items)) window[area].setItem(k, v); }} catch (e) {{}} }} }})();",
+            storage,
+        );
+        let added = page.execute(AddScriptToEvaluateOnNewDocumentParams::new(source)).await?;
+        Ok(Some(added.result.identifier))
+    }
+
+    /// Store the cookies of the tab's browser context and the web storage of the current page in the session.
+    async fn save_session(&self, page: &PageLease, id: &str) -> Result<(), ScrapeError> {
+        let cookies = page
+            .execute(GetCookiesParams { browser_context_id: Some(page.context_id().clone()) })
+            .await?
+            .result
+            .cookies
+            .into_iter()
+            .map(|c| SessionCookie {
+                expires: (!c.session && c.expires > 0.0).then_some(c.expires),
+                same_site: c.same_site.map(|s| s.as_ref().to_string()),
+                name: c.name,
+                value: c.value,
+                domain: c.domain,
+                path: c.path,
+                http_only: c.http_only,
+                secure: c.secure,
+            })
+            .collect();
+        let snapshot = "(() => { const read = (area) => { try { const s = window[area]; \
+            return Object.fromEntries(Object.keys(s).map((k) => [k, s.getItem(k)])); } catch (e) { return {}; } }; \
+            return { origin: location.origin, local: read('localStorage'), session: read('sessionStorage') }; })()";
+        let storage = page
+            .evaluate_expression(snapshot)
+            .await
+            .ok()
+            .and_then(|r| r.into_value::<OriginStorage>().ok())
+            .filter(|s| s.origin.starts_with("http://") || s.origin.starts_with("https://"));
+        self.sessions.save(id, cookies, storage)?;
+        Ok(())
+    }
+
+    /// Save the request's session (if any) and give the tab back.
+    /// Session tabs are closed instead of reused, since their storage spans several origins.
+    async fn finish(&self, page: PageLease, options: &RequestOptions) -> Result<(), ScrapeError> {
+        match &options.session {
+            Some(id) => {
+                let saved = self.save_session(&page, id).await;
+                drop(page);
+                saved
+            }
+            None => {
+                page.release().await;
+                Ok(())
+            }
+        }
+    }
+
+    /// Wait for `conditions` within the selector timeout.
+    /// Returns whether they timed out on a partial request; otherwise that is an error.
+    async fn settle(nav: &Navigation, conditions: &[WaitCondition], budget: &Budget) -> Result<bool, ScrapeError> {
+        let limit = budget.limit(budget.timeouts.selector);
+        match wait::wait_until(&nav.page, &nav.recorder, conditions, limit).await {
+            Ok(()) => Ok(false),
+            Err(_) if budget.partial => Ok(true),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Run the request's actions, or mark them skipped when the page is only partially loaded.
+    async fn perform(nav: &Navigation, actions: &[Action], budget: &Budget, timed_out: bool) -> Vec<ActionResult> {
+        if timed_out {
+            return actions::skipped(actions);
+        }
+        actions::run(&nav.page, &nav.recorder, actions, budget.timeouts.selector, budget.deadline).await
+    }
+
+    /// Expand infinite scroll and lazy images, unless the page is only partially loaded.
+    async fn expand(nav: &Navigation, auto_scroll: &Option<AutoScroll>, budget: &Budget, timed_out: bool) -> Result<Option<ScrollReport>, ScrapeError> {
+        match auto_scroll {
+            Some(config) if !timed_out => scroll::expand(&nav.page, config, budget.deadline).await.map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Load `url` for capturing or printing: wait conditions, actions (any required
+    /// failure fails the request) and auto-scroll.
+    async fn prepare(&self, url: &str, budget: &Budget, options: &RequestOptions) -> Result<Navigation, ScrapeError> {
+        let proxy = self.select_proxy(url, options)?;
+        let nav = self.navigate(url, budget, options, proxy.as_ref()).await?;
+        Self::settle(&nav, &options.wait_for, budget).await?;
+        let results = Self::perform(&nav, &options.actions, budget, false).await;
+        if let Some(e) = actions::failure(&options.actions, &results) {
+            return Err(e);
+        }
+        Self::expand(&nav, &options.auto_scroll, budget, false).await?;
+        Ok(nav)
+    }
+
+    /// Actions and auto-scroll need the browser: refuse them on requests that never render.
+    fn require_render(options: &RequestOptions) -> Result<(), ScrapeError> {
+        if options.needs_browser() {
+            Err(ScrapeError::invalid_request("actions and auto_scroll need browser rendering"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Find `selector` on the page, failing with `SelectorNotFound` when nothing matches.
+    async fn find(page: &Page, selector: &str) -> Result<Element, ScrapeError> {
+        page.find_element(selector)
+            .await
+            .map_err(|e| ScrapeError::new(ErrorCode::SelectorNotFound, format!("selector '{}' not found: {}", selector, e)))
+    }
+
+    /// Poll for `selector` until it appears or the selector timeout expires.
+    async fn wait_for(page: &Page, selector: &str, budget: &Budget) -> Result<Element, ScrapeError> {
+        let limit = budget.limit(budget.timeouts.selector);
+        let deadline = Instant::now() + limit;
+        loop {
+            match page.find_element(selector).await {
+                Ok(element) => return Ok(element),
+                Err(_) if Instant::now() < deadline => tokio::time::sleep(SELECTOR_POLL_INTERVAL).await,
+                Err(e) => {
+                    return Err(ScrapeError::new(
+                        ErrorCode::SelectorNotFound,
+                        format!("selector '{}' not found after {}ms: {}", selector, limit.as_millis(), e),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Screenshot `region` (CSS pixels, page coordinates) in the requested format,
+    /// scaled down to fit `image`'s size limits.
+    async fn screenshot(page: &Page, region: ClipRect, beyond_viewport: bool, image: &CaptureOptions) -> Result<Vec<u8>, ScrapeError> {
+        let dpr = page.evaluate_expression("window.devicePixelRatio")
+            .await?
+            .into_value::<f64>()
+            .unwrap_or(1.0);
+        let region = region.snap(dpr);
+        let scale = image.scale_for(region.width * dpr, region.height * dpr);
+
+        let clip = ViewportBuilder::default()
+            .x(region.x)
+            .y(region.y)
+            .width(region.width)
+            .height(region.height)
+            .scale(scale)
             .build()?;
-        let page = b.new_page(target_params).await?;
-        page.emulate_geolocation(
-            SetGeolocationOverrideParamsBuilder::default()
-                // 大阪日本橋 err 100m
-                .latitude(34.6676)
-                .longitude(135.5063)
-                .accuracy(100)
-                .build()
-        ).await?;
-        page.emulate_timezone(
-            SetTimezoneOverrideParamsBuilder::default()
-                .timezone_id("Asia/Tokyo")
-                .build()?
-        ).await?;
-        page.enable_stealth_mode_with_agent(Self::UA).await?;
-        Ok(page)
+
+        let format = image.format();
+        let mut params = ScreenshotParamsBuilder::default()
+            .format(screenshot_format(format))
+            .clip(clip)
+            .capture_beyond_viewport(beyond_viewport);
+        if format != ImageFormat::Png {
+            params = params.quality(image.quality.unwrap_or(DEFAULT_IMAGE_QUALITY));
+        }
+
+        Ok(page.screenshot(params.build()).await?)
     }
 
     pub async fn capture_element(
@@ -87,58 +954,208 @@ impl Engine {
         url: &str,
         selector: &str,
         wait: Duration,
-    ) -> Result<Vec<u8>, Box<dyn Error>> {
-        let page = self.new_page(url).await?;
+        image: &CaptureOptions,
+        options: &RequestOptions,
+    ) -> Result<Vec<u8>, ScrapeError> {
+        image.validate()?;
+        if image.clip.is_some() {
+            return Err(ScrapeError::invalid_request("clip cannot be combined with selector"));
+        }
+        let budget = self.budget(options);
+        self.guarded(budget.run(async {
+            let nav = self.prepare(url, &budget, options).await?;
+            // keep `nav` (and with it the request interceptor) alive until the tab is released
+            let page = &nav.page;
 
-        page.wait_for_navigation().await?;
+            tokio::time::sleep(budget.limit(wait)).await;
 
-        tokio::time::sleep(wait).await;
+            let element = Self::wait_for(page, selector, &budget).await?;
 
-        let element = page.find_element(selector).await?;
+            let (region, page_size) = Self::element_region(&element, selector).await?;
+            let region = match image.padding {
+                Some(padding) => region.pad(padding, page_size.0, page_size.1),
+                None => region,
+            };
 
-        let bounding_box = element.bounding_box().await?;
+            let image_data = Self::screenshot(page, region, true, image).await?;
 
-        let viewport = ViewportBuilder::default()
-            .x(bounding_box.x)
-            .y(bounding_box.y)
-            .width(bounding_box.width)
-            .height(bounding_box.height)
-            .scale(1.0)
-            .build()?;
+            self.finish(nav.page, options).await?;
 
-        let format = ScreenshotParamsBuilder::default()
-            .format(CaptureScreenshotFormat::Png)
-            .clip(viewport)
-            .build();
+            Ok(image_data)
+        })).await
+    }
 
-        let png_data = page.screenshot(format).await?;
+    /// Capture every element matching any of `selectors`, in selector then document order.
+    ///
+    /// Fails with `SelectorNotFound` only when no selector matches at all; selectors without
+    /// matches and zero-size elements are listed in the manifest instead.
+    pub async fn capture_elements(
+        &self,
+        url: &str,
+        selectors: &[String],
+        wait: Duration,
+        image: &CaptureOptions,
+        options: &RequestOptions,
+    ) -> Result<ElementCaptures, ScrapeError> {
+        image.validate()?;
+        if selectors.is_empty() {
+            return Err(ScrapeError::invalid_request("no selectors to capture"));
+        }
+        if image.clip.is_some() {
+            return Err(ScrapeError::invalid_request("clip cannot be combined with selector"));
+        }
+        let max_elements = image.max_elements.unwrap_or(DEFAULT_MAX_ELEMENTS);
+        let budget = self.budget(options);
+        self.guarded(budget.run(async {
+            let nav = self.prepare(url, &budget, options).await?;
+            // keep `nav` (and with it the request interceptor) alive until the tab is released
+            let page = &nav.page;
 
-        page.close().await?;
+            tokio::time::sleep(budget.limit(wait)).await;
+
+            // wait until at least one of the selectors matches
+            let any = WaitCondition::Any {
+                conditions: selectors.iter().map(|selector| WaitCondition::Selector { selector: selector.clone() }).collect(),
+            };
+            let limit = budget.limit(budget.timeouts.selector);
+            if wait::wait_until(page, &nav.recorder, std::slice::from_ref(&any), limit).await.is_err() {
+                return Err(ScrapeError::new(
+                    ErrorCode::SelectorNotFound,
+                    format!("no element matches any of {} after {}ms", selectors.join(", "), limit.as_millis()),
+                ));
+            }
+
+            let format = image.format();
+            let mut captures = ElementCaptures::default();
+            captures.manifest.format = format;
+            'selectors: for selector in selectors {
+                let elements = page.find_elements(selector.as_str()).await.unwrap_or_default();
+                if elements.is_empty() {
+                    captures.manifest.missing.push(selector.clone());
+                }
+                for (match_index, element) in elements.iter().enumerate() {
+                    if captures.images.len() >= max_elements {
+                        captures.manifest.truncated = true;
+                        break 'selectors;
+                    }
+                    let (bounding_box, page_size) = match Self::element_region(element, selector).await {
+                        Ok(region) => region,
+                        Err(e) if e.code == ErrorCode::ElementNotVisible => {
+                            captures.manifest.empty.push(ElementRef { selector: selector.clone(), match_index });
+                            continue;
+                        }
+                        Err(e) => return Err(e),
+                    };
+                    let clip = match image.padding {
+                        Some(padding) => bounding_box.pad(padding, page_size.0, page_size.1),
+                        None => bounding_box,
+                    };
+                    let index = captures.images.len();
+                    captures.images.push(Self::screenshot(page, clip, true, image).await?);
+                    captures.manifest.elements.push(ElementCaptureInfo {
+                        index,
+                        selector: selector.clone(),
+                        match_index,
+                        file: format!("element-{}.{}", index, format.extension()),
+                        bounding_box,
+                        clip,
+                    });
+                }
+            }
 
-        Ok(png_data)
+            self.finish(nav.page, options).await?;
+
+            Ok(captures)
+        })).await
+    }
+
+    /// Scroll `element` into view and return its box in page coordinates, with the page size.
+    ///
+    /// Fails with `ElementNotVisible` when it has no box, zero size or is hidden,
+    /// instead of capturing a blank area.
+    async fn element_region(element: &Element, selector: &str) -> Result<(ClipRect, (f64, f64)), ScrapeError> {
+        let not_visible = |reason: &str| {
+            ScrapeError::new(ErrorCode::ElementNotVisible, format!("element '{}' is not visible: {}", selector, reason))
+        };
+        let returned = element.call_js_fn(ELEMENT_GEOMETRY_SCRIPT, true).await?;
+        let json = returned.result.value.and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default();
+        let geometry = serde_json::from_str::<Option<ElementGeometry>>(&json)
+            .map_err(|e| ScrapeError::from(format!("Failed to measure element '{}': {}", selector, e)))?
+            .ok_or_else(|| not_visible("detached from the document"))?;
+        if geometry.hidden {
+            return Err(not_visible("hidden"));
+        }
+        if geometry.width.is_nan() || geometry.height.is_nan() || geometry.width <= 0.0 || geometry.height <= 0.0 {
+            return Err(not_visible(&format!("zero size ({}x{})", geometry.width, geometry.height)));
+        }
+        let region = ClipRect { x: geometry.x, y: geometry.y, width: geometry.width, height: geometry.height };
+        Ok((region, (geometry.page_width, geometry.page_height)))
     }
 
+    /// Capture the whole page (or `image.clip`, or the first screen for a thumbnail).
+    ///
+    /// Captures beyond the viewport instead of resizing it, so the emulated
+    /// device metrics stay in effect.
     pub async fn capture_full_page(
         &self,
         url: &str,
         wait: Duration,
-    ) -> Result<Vec<u8>, Box<dyn Error>> {
-        let page = self.new_page(url).await?;
+        image: &CaptureOptions,
+        options: &RequestOptions,
+    ) -> Result<Vec<u8>, ScrapeError> {
+        image.validate()?;
+        let budget = self.budget(options);
+        self.guarded(budget.run(async {
+            let nav = self.prepare(url, &budget, options).await?;
+            // keep `nav` (and with it the request interceptor) alive until the tab is released
+            let page = &nav.page;
+
+            tokio::time::sleep(budget.limit(wait)).await;
+
+            let metrics = page.layout_metrics().await?;
+            let region = match (image.clip, image.thumbnail) {
+                (Some(clip), _) => clip,
+                (None, Some(_)) => {
+                    let viewport = &metrics.css_visual_viewport;
+                    ClipRect { x: viewport.page_x, y: viewport.page_y, width: viewport.client_width, height: viewport.client_height }
+                }
+                (None, None) => {
+                    let content = &metrics.css_content_size;
+                    ClipRect { x: 0.0, y: 0.0, width: content.width, height: content.height }
+                }
+            };
 
-        page.wait_for_navigation().await?;
+            let image_data = Self::screenshot(page, region, image.thumbnail.is_none(), image).await?;
 
-        tokio::time::sleep(wait).await;
+            self.finish(nav.page, options).await?;
 
-        let format = ScreenshotParamsBuilder::default()
-            .format(CaptureScreenshotFormat::Png)
-            .full_page(true)
-            .build();
+            Ok(image_data)
+        })).await
+    }
 
-        let png_data = page.screenshot(format).await?;
+    /// Print the page to PDF (`Page.printToPDF`). Only works with headless Chromium.
+    pub async fn pdf(
+        &self,
+        url: &str,
+        wait: Duration,
+        pdf: &PdfOptions,
+        options: &RequestOptions,
+    ) -> Result<Vec<u8>, ScrapeError> {
+        pdf.validate()?;
+        let budget = self.budget(options);
+        self.guarded(budget.run(async {
+            let nav = self.prepare(url, &budget, options).await?;
+            // keep `nav` (and with it the request interceptor) alive until the tab is released
+            let page = &nav.page;
 
-        page.close().await?;
+            tokio::time::sleep(budget.limit(wait)).await;
 
-        Ok(png_data)
+            let pdf_data = page.pdf(print_params(pdf)).await?;
+
+            self.finish(nav.page, options).await?;
+
+            Ok(pdf_data)
+        })).await
     }
 
     pub async fn scraping(
@@ -147,72 +1164,222 @@ impl Engine {
         selector: Vec<&str>,
         text_selector: Option<&str>,
         waiting_selector: Option<&str>,
-    ) -> Result<ScrapeResults, Box<dyn Error>> {
-        let page = self.new_page(url).await?;
+        render: RenderMode,
+        options: &RequestOptions,
+    ) -> Result<ScrapeResults, ScrapeError> {
+        let budget = self.budget(options);
+        let proxy = self.select_proxy(url, options)?;
+        self.check_robots(url, &budget, options, proxy.as_ref()).await?;
 
-        page.wait_for_navigation().await?;
+        if render != RenderMode::Always {
+            if render == RenderMode::Never {
+                Self::require_render(options)?;
+            }
+            let fetched = budget.run(self.fetch_static(&utils::url_decode(url), &budget, proxy.as_ref())).await;
+            match fetched {
+                // auto: pages that need actions or scrolling are always rendered
+                Ok(fetched) if render == RenderMode::Never || (!options.needs_browser() && fetched.contains(waiting_selector.unwrap_or("html"))) => {
+                    let mut results = extract::extract(&fetched.document, &fetched.url, &selector, text_selector)?;
+                    fetched.response.fill(&mut results);
+                    return Ok(results);
+                }
+                Err(e) if render == RenderMode::Never => return Err(e),
+                // auto: the static HTML is not enough, render it
+                _ => {}
+            }
+        }
 
-        page.find_element(waiting_selector.unwrap_or("html")).await?;
+        self.guarded(budget.run(async {
+            let nav = self.navigate(url, &budget, options, proxy.as_ref()).await?;
+            let mut timed_out = nav.timed_out;
 
-        let url = page.url().await?.ok_or("URL is None")?;
-        let base_url = url.split('/').take(3).collect::<Vec<&str>>().join("/");
+            if !timed_out {
+                timed_out = Self::settle(&nav, &options.wait_for, &budget).await?;
+            }
+            let action_results = Self::perform(&nav, &options.actions, &budget, timed_out).await;
+            let scroll = Self::expand(&nav, &options.auto_scroll, &budget, timed_out).await?;
+            // the waiting selector may only appear after the actions (e.g. a "more" button)
+            if let (false, Some(selector)) = (timed_out, waiting_selector) {
+                let conditions = [WaitCondition::Selector { selector: selector.to_string() }];
+                timed_out = Self::settle(&nav, &conditions, &budget).await?;
+            }
+            let Navigation { page, response, .. } = nav;
 
-        let document = page.content().await?;
-        let text_element = page.find_element(text_selector.unwrap_or("html")).await?;
-        let text = text_element.inner_text().await?.unwrap_or(String::new());
-        page.close().await?;
+            let url = page.url().await?.ok_or("URL is None")?;
 
-        // parse ready
-        let fragments = Html::parse_document(&document);
+            let document = page.content().await?;
+            let text = match Self::find(&page, text_selector.unwrap_or("html")).await {
+                Ok(text_element) => text_element.inner_text().await?.unwrap_or(String::new()),
+                // the text root may not exist yet in a partially loaded page
+                Err(_) if timed_out => String::new(),
+                Err(e) => return Err(e),
+            };
+            self.finish(page, options).await?;
 
+            let mut results = extract::extract_with_text(document, url, text, &selector)?;
+            response.fill(&mut results);
+            results.partial = timed_out;
+            results.actions = action_results;
+            results.scroll = scroll;
+            Ok(results)
+        })).await
+    }
 
-        // selectors
-        let links_selector = Selector::parse("a[href]").unwrap();
-        let favicon_selector = Selector::parse(r#"link[rel="icon"]"#).unwrap();
-        let title_selector = Selector::parse("title").unwrap();
-        let lang_selector = Selector::parse("html").unwrap();
+    /// Render `spec.url` and evaluate the declarative selectors in `spec` against it.
+    ///
+    /// `render.timeout_ms` of the spec is the navigation timeout unless
+    /// `navigation_timeout_ms` overrides it.
+    pub async fn scrape_spec(&self, spec: &ScrapeSpec) -> Result<SpecResults, ScrapeError> {
+        spec.validate()?;
+        let options = RequestOptions {
+            navigation_timeout_ms: spec.options.navigation_timeout_ms.or(spec.render.timeout_ms),
+            ..spec.options.clone()
+        };
+        let budget = self.budget(&options);
+        let proxy = self.select_proxy(&spec.url, &options)?;
+        self.check_robots(&spec.url, &budget, &options, proxy.as_ref()).await?;
 
-        let mut links: Vec<String> = fragments.select(&links_selector)
-            .filter_map(|elem| elem.value().attr("href"))
-            .map(|href| utils::url_normalize(&base_url, href))
-            .collect();
+        if !spec.render.enabled {
+            let fetched = budget.run(self.fetch_static(&spec.url, &budget, proxy.as_ref())).await?;
+            let results = spec::evaluate(spec, &fetched.document, &fetched.url)?;
+            return Ok(SpecResults {
+                status: fetched.response.status.unwrap_or(200),
+                url: fetched.url,
+                results,
+                partial: false,
+                actions: Vec::new(),
+                scroll: None,
+            });
+        }
 
-        let favicon: Option<String> = fragments.select(&favicon_selector)
-            .filter_map(|elem| elem.value().attr("href"))
-            .map(|href| utils::url_normalize(&base_url, href))
-            .next();
+        self.guarded(budget.run(async {
+            let nav = self.navigate(&spec.url, &budget, &options, proxy.as_ref()).await?;
+            let mut timed_out = nav.timed_out;
+            if !timed_out {
+                timed_out = Self::settle(&nav, &options.wait_for, &budget).await?;
+            }
+            let action_results = Self::perform(&nav, &options.actions, &budget, timed_out).await;
+            let scroll = Self::expand(&nav, &options.auto_scroll, &budget, timed_out).await?;
+            let Navigation { page, response, .. } = nav;
 
-        let title: Option<String> = fragments.select(&title_selector)
-            .filter_map(|elem| Some(elem.inner_html()))
-            .next();
+            if let Some(wait_ms) = spec.render.wait_ms {
+                tokio::time::sleep(budget.limit(Duration::from_millis(wait_ms))).await;
+            }
 
-        let lang: Option<String> = fragments.select(&lang_selector)
-            .filter_map(|elem| elem.value().attr("lang").map(|s| s.to_string()))
-            .next();
+            let url = page.url().await?.ok_or("URL is None")?;
+            let document = page.content().await?;
+            self.finish(page, &options).await?;
 
-        let contents: HashMap<String, Vec<String>> = selector
-            .iter()
-            .map(|s| {
-                let sel = Selector::parse(s).unwrap();
-                let texts: Vec<String> = fragments.select(&sel)
-                    .map(|elem| elem.text().collect::<String>().trim().to_string())
-                    .collect();
+            let results = spec::evaluate(spec, &document, &url)?;
 
-                (s.to_string(), texts)
+            Ok(SpecResults {
+                status: response.status.unwrap_or(200),
+                url,
+                results,
+                partial: timed_out,
+                actions: action_results,
+                scroll,
             })
-            .collect();
+        })).await
+    }
+}
 
-        links.sort();
-
-        Ok(ScrapeResults {
-            url,
-            title,
-            contents,
-            lang,
-            favicon,
-            links,
-            document,
-            text,
-        })
+fn screenshot_format(format: ImageFormat) -> CaptureScreenshotFormat {
+    match format {
+        ImageFormat::Png => CaptureScreenshotFormat::Png,
+        ImageFormat::Jpeg => CaptureScreenshotFormat::Jpeg,
+        ImageFormat::Webp => CaptureScreenshotFormat::Webp,
+    }
+}
+
+/// `Page.printToPDF` parameters (CDP takes inches)
+fn print_params(pdf: &PdfOptions) -> PrintToPdfParams {
+    let inches = |mm: f64| mm / 25.4;
+    let (width, height) = pdf.paper_size_mm();
+    let [top, right, bottom, left] = pdf.margins_mm();
+    let header_footer = pdf.header_template.is_some() || pdf.footer_template.is_some();
+    // with only one template given, an empty one hides the other part instead of Chromium's default
+    let template = |t: &Option<String>| header_footer.then(|| t.clone().unwrap_or_else(|| "<span></span>".to_string()));
+    PrintToPdfParams {
+        landscape: Some(pdf.landscape),
+        display_header_footer: Some(header_footer),
+        print_background: Some(pdf.print_background),
+        scale: pdf.scale,
+        paper_width: Some(inches(width)),
+        paper_height: Some(inches(height)),
+        margin_top: Some(inches(top)),
+        margin_bottom: Some(inches(bottom)),
+        margin_left: Some(inches(left)),
+        margin_right: Some(inches(right)),
+        page_ranges: pdf.page_ranges.clone(),
+        header_template: template(&pdf.header_template),
+        footer_template: template(&pdf.footer_template),
+        prefer_css_page_size: Some(pdf.prefer_css_page_size),
+        ..Default::default()
+    }
+}
+
+/// CDP form of a stored cookie
+fn cookie_param(cookie: &SessionCookie) -> Result<CookieParam, String> {
+    let mut param = CookieParam::builder()
+        .name(cookie.name.clone())
+        .value(cookie.value.clone())
+        .domain(cookie.domain.clone())
+        .path(cookie.path.clone())
+        .secure(cookie.secure)
+        .http_only(cookie.http_only);
+    if let Some(expires) = cookie.expires {
+        param = param.expires(TimeSinceEpoch::new(expires));
+    }
+    let same_site = match cookie.same_site.as_deref().map(str::to_ascii_lowercase).as_deref() {
+        Some("strict") => Some(CookieSameSite::Strict),
+        Some("lax") => Some(CookieSameSite::Lax),
+        Some("none") | Some("no_restriction") => Some(CookieSameSite::None),
+        _ => None,
+    };
+    if let Some(same_site) = same_site {
+        param = param.same_site(same_site);
+    }
+    param.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn element_geometry() {
+        let geometry: Option<ElementGeometry> = serde_json::from_str(
+            r#"{"x": 8.5, "y": 2400, "width": 320, "height": 0, "hidden": false, "page_width": 1280, "page_height": 5000}"#,
+        )
+        .unwrap();
+        let geometry = geometry.unwrap();
+        assert_eq!((geometry.x, geometry.y, geometry.height), (8.5, 2400.0, 0.0));
+        assert!(!geometry.hidden);
+        // detached elements report null
+        assert!(serde_json::from_str::<Option<ElementGeometry>>("null").unwrap().is_none());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn resolve_timeouts() {
+        let config = TimeoutConfig::default();
+        let t = config.resolve(&RequestOptions::default());
+        assert_eq!(t.total, config.default_total);
+        assert_eq!(t.navigation, config.default_navigation);
+
+        // clamped to the maxima, and no step outlives the whole request
+        let options = RequestOptions {
+            timeout_ms: Some(10_000),
+            navigation_timeout_ms: Some(20_000),
+            selector_timeout_ms: Some(3_600_000),
+            ..Default::default()
+        };
+        let t = config.resolve(&options);
+        assert_eq!(t.total, Duration::from_secs(10));
+        assert_eq!(t.navigation, Duration::from_secs(10));
+        assert_eq!(t.selector, Duration::from_secs(10));
+
+        let t = config.resolve(&RequestOptions { timeout_ms: Some(u64::MAX), ..Default::default() });
+        assert_eq!(t.total, config.max_total);
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::{HashSet, VecDeque};
+
+use regex::Regex;
+
+use super::browser::Engine;
+use super::error::ScrapeError;
+use super::schema::{CrawlRequest, CrawlResults, CrawlScope, CrawledPage, ScopeDomain, ScraperResult};
+
+use super::utils;
+
+/// Compiled `CrawlScope`, anchored to the hosts of the seed URLs.
+pub struct Scope {
+    domain: ScopeDomain,
+    path_prefix: Option<String>,
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+    hosts: HashSet<String>,
+    domains: HashSet<String>,
+}
+
+impl Scope {
+    pub fn new(scope: &CrawlScope, seeds: &[String]) -> Result<Self, ScrapeError> {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|p| Regex::new(p).map_err(|e| ScrapeError::invalid_request(format!("invalid scope pattern '{}': {}", p, e))))
+                .collect::<Result<Vec<Regex>, ScrapeError>>()
+        };
+        let hosts: HashSet<String> = seeds.iter().filter_map(|s| utils::url_host(s)).collect();
+        let domains = hosts.iter().map(|h| utils::registrable_domain(h)).collect();
+        Ok(Scope {
+            domain: scope.domain,
+            path_prefix: scope.path_prefix.clone(),
+            include: compile(&scope.include)?,
+            exclude: compile(&scope.exclude)?,
+            hosts,
+            domains,
+        })
+    }
+
+    /// Whether `url` should be followed.
+    pub fn allows(&self, url: &str) -> bool {
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return false;
+        }
+        let Some(host) = utils::url_host(url) else {
+            return false;
+        };
+        let in_domain = match self.domain {
+            ScopeDomain::SameHost => self.hosts.contains(&host),
+            ScopeDomain::SameDomain => self.domains.contains(&utils::registrable_domain(&host)),
+            ScopeDomain::Any => true,
+        };
+        if !in_domain {
+            return false;
+        }
+        if let Some(prefix) = &self.path_prefix
+            && !utils::url_path(url).starts_with(prefix.as_str())
+        {
+            return false;
+        }
+        if !self.include.is_empty() && !self.include.iter().any(|r| r.is_match(url)) {
+            return false;
+        }
+        !self.exclude.iter().any(|r| r.is_match(url))
+    }
+}
+
+/// Run a crawl job to completion.
+pub async fn crawl(engine: &Engine, req: &CrawlRequest) -> Result<CrawlResults, ScrapeError> {
+    crawl_with(engine, req, |_| true).await
+}
+
+/// Run a crawl job, calling `on_page` after every fetched page.
+/// Returning `false` from `on_page` stops the crawl early.
+pub async fn crawl_with(
+    engine: &Engine,
+    req: &CrawlRequest,
+    mut on_page: impl FnMut(&CrawledPage) -> bool,
+) -> Result<CrawlResults, ScrapeError> {
+    req.validate()?;
+    let scope = Scope::new(&req.scope, &req.seeds)?;
+    let selectors = req.selectors.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
+
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut frontier: VecDeque<(String, usize)> = VecDeque::new();
+    for seed in &req.seeds {
+        let Some(seed) = utils::url_canonicalize(seed, req.scope.strip_tracking_params) else {
+            return Err(ScrapeError::invalid_request(format!("invalid seed url: {}", seed)));
+        };
+        if seen.insert(seed.clone()) {
+            frontier.push_back((seed, 0));
+        }
+    }
+
+    let mut pages: Vec<CrawledPage> = Vec::new();
+    while !frontier.is_empty() && pages.len() < req.max_pages {
+        // breadth first: the frontier is ordered by depth
+        let budget = (req.max_pages - pages.len()).min(req.concurrency.max(1)).min(frontier.len());
+        let batch: Vec<(String, usize)> = frontier.drain(..budget).collect();
+
+        let fetched = futures::future::join_all(batch.into_iter().map(|(url, depth)| {
+            let selectors = selectors.clone();
+            async move {
+                let result = engine
+                    .scraping(&url, selectors, req.text_selector.as_deref(), req.waiting_selector.as_deref(), req.render, &req.options)
+                    .await;
+                (url, depth, result)
+            }
+        }))
+        .await;
+
+        for (url, depth, result) in fetched {
+            let result = match result {
+                Ok(results) => {
+                    if depth < req.max_depth {
+                        // links are already resolved; canonicalize again so tracking params don't create duplicates
+                        for link in &results.links {
+                            let Some(link) = utils::url_canonicalize(link, req.scope.strip_tracking_params) else {
+                                continue;
+                            };
+                            if scope.allows(&link) && seen.insert(link.clone()) {
+                                frontier.push_back((link, depth + 1));
+                            }
+                        }
+                    }
+                    ScraperResult::Success { status: results.status.unwrap_or(200), url: url.clone(), results }
+                }
+                Err(e) => e.context("Error during scraping").into(),
+            };
+            let page = CrawledPage { url, depth, result };
+            let keep_going = on_page(&page);
+            pages.push(page);
+            if !keep_going {
+                return Ok(CrawlResults { pages });
+            }
+        }
+    }
+
+    Ok(CrawlResults { pages })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(json: &str) -> Scope {
+        let scope: CrawlScope = serde_json::from_str(json).unwrap();
+        Scope::new(&scope, &["https://www.example.co.jp/docs/".to_string()]).unwrap()
+    }
+
+    #[test]
+    fn same_host() {
+        let s = scope("{}");
+        assert!(s.allows("https://www.example.co.jp/other"));
+        assert!(!s.allows("https://blog.example.co.jp/"));
+        assert!(!s.allows("mailto:info@example.co.jp"));
+    }
+
+    #[test]
+    fn same_domain_and_prefix() {
+        let s = scope(r#"{ "domain": "same_domain" }"#);
+        assert!(s.allows("https://blog.example.co.jp/"));
+        assert!(!s.allows("https://other.co.jp/"));
+
+        let s = scope(r#"{ "path_prefix": "/docs/" }"#);
+        assert!(s.allows("https://www.example.co.jp/docs/a"));
+        assert!(!s.allows("https://www.example.co.jp/blog/a"));
+    }
+
+    #[test]
+    fn include_exclude() {
+        let s = scope(r#"{ "domain": "any", "include": ["/docs/"], "exclude": ["\\.pdf$"] }"#);
+        assert!(s.allows("https://elsewhere.example/docs/a"));
+        assert!(!s.allows("https://elsewhere.example/docs/a.pdf"));
+        assert!(!s.allows("https://elsewhere.example/blog"));
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        let scope: CrawlScope = serde_json::from_str(r#"{ "include": ["("] }"#).unwrap();
+        assert!(Scope::new(&scope, &[]).is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use super::error::ScrapeError;
+use super::schema::{Emulation, Geolocation};
+
+/// User agent of the `desktop` profile (and of the static HTML fetcher)
+pub const DESKTOP_UA: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36 (+https://371tti.net)";
+
+const IPAD_UA: &str = "Mozilla/5.0 (iPad; CPU OS 17_4 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.4 Mobile/15E148 Safari/604.1 (+https://371tti.net)";
+const IPHONE_UA: &str = "Mozilla/5.0 (iPhone; CPU iPhone OS 17_4 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.4 Mobile/15E148 Safari/604.1 (+https://371tti.net)";
+const PIXEL_UA: &str = "Mozilla/5.0 (Linux; Android 14; Pixel 8) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Mobile Safari/537.36 (+https://371tti.net)";
+const GALAXY_UA: &str = "Mozilla/5.0 (Linux; Android 14; SM-S911B) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Mobile Safari/537.36 (+https://371tti.net)";
+
+/// A named device to emulate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeviceProfile {
+    pub name: &'static str,
+    /// CSS pixels
+    pub width: u32,
+    pub height: u32,
+    pub device_scale_factor: f64,
+    /// mobile viewport (meta viewport, overlay scrollbars)
+    pub mobile: bool,
+    pub touch: bool,
+    pub user_agent: &'static str,
+}
+
+/// Built-in profiles
+pub const DEVICES: &[DeviceProfile] = &[
+    DeviceProfile { name: "desktop", width: 2560, height: 1440, device_scale_factor: 1.0, mobile: false, touch: false, user_agent: DESKTOP_UA },
+    DeviceProfile { name: "laptop", width: 1366, height: 768, device_scale_factor: 1.0, mobile: false, touch: false, user_agent: DESKTOP_UA },
+    DeviceProfile { name: "tablet", width: 820, height: 1180, device_scale_factor: 2.0, mobile: true, touch: true, user_agent: IPAD_UA },
+    DeviceProfile { name: "iphone_se", width: 375, height: 667, device_scale_factor: 2.0, mobile: true, touch: true, user_agent: IPHONE_UA },
+    DeviceProfile { name: "iphone_15", width: 393, height: 852, device_scale_factor: 3.0, mobile: true, touch: true, user_agent: IPHONE_UA },
+    DeviceProfile { name: "pixel_8", width: 412, height: 915, device_scale_factor: 2.625, mobile: true, touch: true, user_agent: PIXEL_UA },
+    DeviceProfile { name: "galaxy_s23", width: 360, height: 780, device_scale_factor: 3.0, mobile: true, touch: true, user_agent: GALAXY_UA },
+];
+
+impl DeviceProfile {
+    /// Look a profile up by name (`-` and `_` are interchangeable, case-insensitive)
+    pub fn find(name: &str) -> Option<&'static DeviceProfile> {
+        let name = name.trim().to_ascii_lowercase().replace('-', "_");
+        DEVICES.iter().find(|device| device.name == name)
+    }
+}
+
+/// Emulation settings in effect for one page
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedEmulation {
+    pub width: u32,
+    pub height: u32,
+    pub device_scale_factor: f64,
+    pub mobile: bool,
+    pub touch: bool,
+    pub user_agent: String,
+    pub locale: Option<String>,
+    /// `Accept-Language` derived from the locale
+    pub accept_language: Option<String>,
+    pub timezone: Option<String>,
+    pub geolocation: Option<Geolocation>,
+}
+
+impl ResolvedEmulation {
+    /// Start from the device profile (`desktop` when none is named) and apply the explicit overrides.
+    /// An unknown device or an out-of-range value is an `invalid_request`.
+    pub fn resolve(emulation: &Emulation) -> Result<Self, ScrapeError> {
+        let device = match emulation.device.as_deref() {
+            Some(name) => DeviceProfile::find(name).ok_or_else(|| {
+                let names = DEVICES.iter().map(|d| d.name).collect::<Vec<_>>();
+                ScrapeError::invalid_request(format!("unknown device '{}' (available: {})", name, names.join(", ")))
+            })?,
+            None => &DEVICES[0],
+        };
+        let width = emulation.viewport_width.unwrap_or(device.width);
+        let height = emulation.viewport_height.unwrap_or(device.height);
+        if !(1..=10_000).contains(&width) || !(1..=10_000).contains(&height) {
+            return Err(ScrapeError::invalid_request(format!("viewport {}x{} out of range (1-10000)", width, height)));
+        }
+        let device_scale_factor = emulation.device_scale_factor.unwrap_or(device.device_scale_factor);
+        if !(0.25..=5.0).contains(&device_scale_factor) {
+            return Err(ScrapeError::invalid_request(format!("device_scale_factor {} out of range (0.25-5)", device_scale_factor)));
+        }
+        if let Some(geo) = &emulation.geolocation
+            && (!(-90.0..=90.0).contains(&geo.latitude) || !(-180.0..=180.0).contains(&geo.longitude))
+        {
+            return Err(ScrapeError::invalid_request(format!("geolocation {},{} out of range", geo.latitude, geo.longitude)));
+        }
+        let locale = emulation.locale.clone().filter(|l| !l.is_empty());
+        Ok(ResolvedEmulation {
+            width,
+            height,
+            device_scale_factor,
+            mobile: device.mobile,
+            touch: device.touch,
+            user_agent: emulation.user_agent.clone().unwrap_or_else(|| device.user_agent.to_string()),
+            accept_language: locale.as_deref().map(accept_language),
+            locale,
+            timezone: emulation.timezone.clone().filter(|t| !t.is_empty()),
+            geolocation: emulation.geolocation,
+        })
+    }
+}
+
+/// `ja-JP` → `ja-JP,ja;q=0.9`
+fn accept_language(locale: &str) -> String {
+    match locale.split_once(['-', '_']) {
+        Some((language, _)) => format!("{},{};q=0.9", locale, language),
+        None => locale.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_and_overrides() {
+        let desktop = ResolvedEmulation::resolve(&Emulation::default()).unwrap();
+        assert_eq!((desktop.width, desktop.height, desktop.mobile), (2560, 1440, false));
+        assert_eq!(desktop.user_agent, DESKTOP_UA);
+
+        let phone = ResolvedEmulation::resolve(&Emulation {
+            device: Some("Pixel-8".to_string()),
+            viewport_height: Some(700),
+            locale: Some("ja-JP".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!((phone.width, phone.height), (412, 700));
+        assert_eq!(phone.device_scale_factor, 2.625);
+        assert!(phone.mobile && phone.touch);
+        assert_eq!(phone.accept_language.as_deref(), Some("ja-JP,ja;q=0.9"));
+    }
+
+    #[test]
+    fn invalid_emulation() {
+        let unknown = Emulation { device: Some("nokia_3310".to_string()), ..Default::default() };
+        assert!(ResolvedEmulation::resolve(&unknown).is_err());
+        let tiny = Emulation { device_scale_factor: Some(0.0), ..Default::default() };
+        assert!(ResolvedEmulation::resolve(&tiny).is_err());
+        let nowhere = Emulation { geolocation: Some(Geolocation { latitude: 91.0, longitude: 0.0, accuracy: 100.0 }), ..Default::default() };
+        assert!(ResolvedEmulation::resolve(&nowhere).is_err());
+    }
+}
@@ -0,0 +1,408 @@
+use std::error::Error;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Machine-readable failure category.
+///
+/// Serialized as a stable snake_case code (e.g. `"blocked_by_robots"`) in
+/// `ScraperResult::Failed` and job errors; new codes may be added, existing ones are never renamed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// Malformed request body or query (missing url, bad JSON, invalid pattern, ...)
+    InvalidRequest,
+    /// A CSS selector could not be parsed
+    InvalidSelector,
+    /// The selector to wait for or capture never matched
+    SelectorNotFound,
+    /// The element to capture matched but has no visible box (zero size, `display: none`, `visibility: hidden`)
+    ElementNotVisible,
+    /// Unknown job or resource
+    NotFound,
+    /// robots.txt disallows the URL
+    BlockedByRobots,
+    /// The document is not HTML
+    UnsupportedContent,
+    /// Navigation or the whole request did not finish in time
+    NavigationTimeout,
+    /// A `wait_for` condition was not met in time
+    WaitTimeout,
+    /// A required step of `actions` failed
+    ActionFailed,
+    /// Host name could not be resolved
+    DnsFailure,
+    /// Connection refused, reset or unreachable
+    ConnectionFailed,
+    /// TLS handshake or certificate error
+    TlsFailure,
+    /// The main document failed to load for another network reason
+    DocumentFailed,
+    /// The proxy refused, could not reach the site or rejected the credentials
+    ProxyFailed,
+    /// Every configured proxy is retired after repeated failures
+    ProxyUnavailable,
+    /// Chromium is down or restarting
+    BrowserUnavailable,
+    /// Chromium was restarted while the request was running
+    BrowserRestarted,
+    /// No capacity left (tab pool or job store)
+    Busy,
+    /// The client could not talk to the API server
+    Transport,
+    #[default]
+    Internal,
+    /// A code this version does not know (sent by a newer server); serialized as `"unknown"`
+    #[serde(other)]
+    Unknown,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::InvalidRequest => "invalid_request",
+            ErrorCode::InvalidSelector => "invalid_selector",
+            ErrorCode::SelectorNotFound => "selector_not_found",
+            ErrorCode::ElementNotVisible => "element_not_visible",
+            ErrorCode::NotFound => "not_found",
+            ErrorCode::BlockedByRobots => "blocked_by_robots",
+            ErrorCode::UnsupportedContent => "unsupported_content",
+            ErrorCode::NavigationTimeout => "navigation_timeout",
+            ErrorCode::WaitTimeout => "wait_timeout",
+            ErrorCode::ActionFailed => "action_failed",
+            ErrorCode::DnsFailure => "dns_failure",
+            ErrorCode::ConnectionFailed => "connection_failed",
+            ErrorCode::TlsFailure => "tls_failure",
+            ErrorCode::DocumentFailed => "document_failed",
+            ErrorCode::ProxyFailed => "proxy_failed",
+            ErrorCode::ProxyUnavailable => "proxy_unavailable",
+            ErrorCode::BrowserUnavailable => "browser_unavailable",
+            ErrorCode::BrowserRestarted => "browser_restarted",
+            ErrorCode::Busy => "busy",
+            ErrorCode::Transport => "transport",
+            ErrorCode::Internal => "internal",
+            ErrorCode::Unknown => "unknown",
+        }
+    }
+
+    /// HTTP status the server answers with for this failure
+    pub fn http_status(&self) -> u16 {
+        match self {
+            ErrorCode::InvalidRequest | ErrorCode::InvalidSelector => 400,
+            ErrorCode::BlockedByRobots => 403,
+            ErrorCode::NotFound => 404,
+            ErrorCode::UnsupportedContent => 415,
+            ErrorCode::SelectorNotFound | ErrorCode::ElementNotVisible | ErrorCode::ActionFailed => 422,
+            ErrorCode::Busy => 429,
+            ErrorCode::DnsFailure
+            | ErrorCode::ConnectionFailed
+            | ErrorCode::TlsFailure
+            | ErrorCode::DocumentFailed
+            | ErrorCode::ProxyFailed
+            | ErrorCode::Transport => 502,
+            ErrorCode::BrowserUnavailable | ErrorCode::BrowserRestarted | ErrorCode::ProxyUnavailable => 503,
+            ErrorCode::NavigationTimeout | ErrorCode::WaitTimeout => 504,
+            ErrorCode::Internal | ErrorCode::Unknown => 500,
+        }
+    }
+
+    /// Category of a Chromium network error (`net::ERR_...`)
+    pub fn from_net_error(text: &str) -> Self {
+        let text = text.trim_start_matches("net::");
+        if text.starts_with("ERR_PROXY_")
+            || text.starts_with("ERR_TUNNEL_CONNECTION_FAILED")
+            || text.starts_with("ERR_SOCKS_")
+            || text.starts_with("ERR_NO_SUPPORTED_PROXIES")
+        {
+            ErrorCode::ProxyFailed
+        } else if text.starts_with("ERR_NAME_NOT_RESOLVED") || text.starts_with("ERR_NAME_RESOLUTION_FAILED") {
+            ErrorCode::DnsFailure
+        } else if text.starts_with("ERR_TIMED_OUT") || text.starts_with("ERR_CONNECTION_TIMED_OUT") {
+            ErrorCode::NavigationTimeout
+        } else if text.starts_with("ERR_CERT_") || text.starts_with("ERR_SSL_") {
+            ErrorCode::TlsFailure
+        } else if text.starts_with("ERR_CONNECTION_")
+            || text.starts_with("ERR_ADDRESS_")
+            || text.starts_with("ERR_INTERNET_DISCONNECTED")
+            || text.starts_with("ERR_EMPTY_RESPONSE")
+        {
+            ErrorCode::ConnectionFailed
+        } else {
+            ErrorCode::DocumentFailed
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Error returned by the engine, the server and the client.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScrapeError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl ScrapeError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        ScrapeError { code, message: message.into() }
+    }
+
+    pub fn invalid_request(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidRequest, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Internal, message)
+    }
+
+    pub fn http_status(&self) -> u16 {
+        self.code.http_status()
+    }
+
+    /// Prefix the message with context, keeping the code.
+    pub fn context(self, context: &str) -> Self {
+        ScrapeError { code: self.code, message: format!("{}: {}", context, self.message) }
+    }
+
+    /// Best-effort category of an untyped error.
+    pub fn classify(e: &(dyn Error + 'static)) -> Self {
+        if let Some(e) = e.downcast_ref::<ScrapeError>() {
+            return e.clone();
+        }
+        if let Some(e) = e.downcast_ref::<BlockedByRobots>() {
+            return e.clone().into();
+        }
+        if let Some(e) = e.downcast_ref::<DocumentError>() {
+            return e.clone().into();
+        }
+        if let Some(e) = e.downcast_ref::<chromiumoxide::error::CdpError>() {
+            return cdp_error(e);
+        }
+        if let Some(e) = e.downcast_ref::<reqwest::Error>() {
+            return reqwest_error(e);
+        }
+        ScrapeError::internal(e.to_string())
+    }
+}
+
+impl fmt::Display for ScrapeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for ScrapeError {}
+
+impl From<Box<dyn Error>> for ScrapeError {
+    fn from(e: Box<dyn Error>) -> Self {
+        ScrapeError::classify(e.as_ref())
+    }
+}
+
+impl From<String> for ScrapeError {
+    fn from(message: String) -> Self {
+        ScrapeError::internal(message)
+    }
+}
+
+impl From<&str> for ScrapeError {
+    fn from(message: &str) -> Self {
+        ScrapeError::internal(message)
+    }
+}
+
+impl From<serde_json::Error> for ScrapeError {
+    fn from(e: serde_json::Error) -> Self {
+        ScrapeError::internal(e.to_string())
+    }
+}
+
+impl From<reqwest::Error> for ScrapeError {
+    fn from(e: reqwest::Error) -> Self {
+        reqwest_error(&e)
+    }
+}
+
+impl From<chromiumoxide::error::CdpError> for ScrapeError {
+    fn from(e: chromiumoxide::error::CdpError) -> Self {
+        cdp_error(&e)
+    }
+}
+
+fn cdp_error(e: &chromiumoxide::error::CdpError) -> ScrapeError {
+    use chromiumoxide::error::CdpError;
+
+    let code = match e {
+        CdpError::Timeout => ErrorCode::NavigationTimeout,
+        CdpError::ChromeMessage(msg) if msg.contains("net::ERR_") => {
+            ErrorCode::from_net_error(&msg[msg.find("net::ERR_").unwrap_or(0)..])
+        }
+        CdpError::Ws(_) | CdpError::NoResponse | CdpError::ChannelSendError(_) => ErrorCode::BrowserUnavailable,
+        _ => ErrorCode::Internal,
+    };
+    ScrapeError::new(code, e.to_string())
+}
+
+fn reqwest_error(e: &reqwest::Error) -> ScrapeError {
+    let code = if e.is_timeout() {
+        ErrorCode::NavigationTimeout
+    } else if e.is_connect() {
+        // the resolver error is only visible in the source chain
+        let mut source = e.source();
+        let mut dns = false;
+        while let Some(s) = source {
+            dns |= s.to_string().contains("dns error");
+            source = s.source();
+        }
+        if dns { ErrorCode::DnsFailure } else { ErrorCode::ConnectionFailed }
+    } else if e.is_redirect() {
+        ErrorCode::DocumentFailed
+    } else {
+        ErrorCode::Internal
+    };
+    ScrapeError::new(code, e.to_string())
+}
+
+/// Returned when robots.txt disallows a URL for our user-agent
+#[derive(Debug, Clone)]
+pub struct BlockedByRobots {
+    pub url: String,
+}
+
+impl fmt::Display for BlockedByRobots {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "blocked by robots.txt: {}", self.url)
+    }
+}
+
+impl Error for BlockedByRobots {}
+
+impl From<BlockedByRobots> for ScrapeError {
+    fn from(e: BlockedByRobots) -> Self {
+        ScrapeError::new(ErrorCode::BlockedByRobots, e.to_string())
+    }
+}
+
+/// Returned when the main document of a navigation could not be loaded at all
+/// (DNS failure, connection refused, aborted, ...)
+#[derive(Debug, Clone)]
+pub struct DocumentError {
+    pub url: String,
+    pub reason: String,
+}
+
+impl fmt::Display for DocumentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "document failed to load: {}: {}", self.url, self.reason)
+    }
+}
+
+impl Error for DocumentError {}
+
+impl From<DocumentError> for ScrapeError {
+    fn from(e: DocumentError) -> Self {
+        ScrapeError::new(ErrorCode::from_net_error(&e.reason), e.to_string())
+    }
+}
+
+/// Error returned by `Client`.
+#[derive(Debug)]
+pub enum ClientError {
+    /// The scrape itself failed (reported by the server or the embedded engine)
+    Scrape(ScrapeError),
+    /// The API server could not be reached or sent an unexpected response
+    Transport(String),
+}
+
+impl ClientError {
+    /// Failure category, `ErrorCode::Transport` when the server was not reached
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ClientError::Scrape(e) => e.code,
+            ClientError::Transport(_) => ErrorCode::Transport,
+        }
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Scrape(e) => write!(f, "{}", e),
+            ClientError::Transport(e) => write!(f, "transport error: {}", e),
+        }
+    }
+}
+
+impl Error for ClientError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ClientError::Scrape(e) => Some(e),
+            ClientError::Transport(_) => None,
+        }
+    }
+}
+
+impl From<ScrapeError> for ClientError {
+    fn from(e: ScrapeError) -> Self {
+        ClientError::Scrape(e)
+    }
+}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(e: reqwest::Error) -> Self {
+        ClientError::Transport(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_are_stable() {
+        for code in [ErrorCode::BlockedByRobots, ErrorCode::NavigationTimeout, ErrorCode::SelectorNotFound] {
+            assert_eq!(serde_json::to_value(code).unwrap(), serde_json::json!(code.as_str()));
+        }
+        assert_eq!(ErrorCode::BlockedByRobots.http_status(), 403);
+        assert_eq!(ErrorCode::NavigationTimeout.http_status(), 504);
+        assert_eq!(ErrorCode::ElementNotVisible.as_str(), "element_not_visible");
+        assert_eq!(ErrorCode::ElementNotVisible.http_status(), 422);
+    }
+
+    #[test]
+    fn unknown_codes() {
+        let code: ErrorCode = serde_json::from_value(serde_json::json!("quota_exceeded")).unwrap();
+        assert_eq!(code, ErrorCode::Unknown);
+        assert_eq!(code.http_status(), 500);
+        assert_eq!(serde_json::to_value(code).unwrap(), serde_json::json!("unknown"));
+        let error: ScrapeError = serde_json::from_str(r#"{"code":"quota_exceeded","message":"over"}"#).unwrap();
+        assert_eq!(error, ScrapeError::new(ErrorCode::Unknown, "over"));
+    }
+
+    #[test]
+    fn net_errors() {
+        assert_eq!(ErrorCode::from_net_error("net::ERR_NAME_NOT_RESOLVED"), ErrorCode::DnsFailure);
+        assert_eq!(ErrorCode::from_net_error("net::ERR_CONNECTION_REFUSED"), ErrorCode::ConnectionFailed);
+        assert_eq!(ErrorCode::from_net_error("net::ERR_CERT_DATE_INVALID"), ErrorCode::TlsFailure);
+        assert_eq!(ErrorCode::from_net_error("net::ERR_TIMED_OUT"), ErrorCode::NavigationTimeout);
+        assert_eq!(ErrorCode::from_net_error("net::ERR_ABORTED"), ErrorCode::DocumentFailed);
+        assert_eq!(ErrorCode::from_net_error("net::ERR_PROXY_CONNECTION_FAILED"), ErrorCode::ProxyFailed);
+        assert_eq!(ErrorCode::from_net_error("net::ERR_TUNNEL_CONNECTION_FAILED"), ErrorCode::ProxyFailed);
+    }
+
+    #[test]
+    fn classify_boxed() {
+        let boxed: Box<dyn Error> = Box::new(ScrapeError::new(ErrorCode::Busy, "full"));
+        assert_eq!(ScrapeError::from(boxed).code, ErrorCode::Busy);
+        let boxed: Box<dyn Error> = Box::new(BlockedByRobots { url: "https://example.com/private".to_string() });
+        assert_eq!(ScrapeError::from(boxed).code, ErrorCode::BlockedByRobots);
+        let boxed: Box<dyn Error> = Box::new(DocumentError { url: "https://example.com".to_string(), reason: "net::ERR_NAME_NOT_RESOLVED".to_string() });
+        assert_eq!(ScrapeError::from(boxed).code, ErrorCode::DnsFailure);
+        let boxed: Box<dyn Error> = "something else".into();
+        assert_eq!(ScrapeError::from(boxed).code, ErrorCode::Internal);
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::{BTreeMap, HashMap};
+
+use scraper::{Html, Selector};
+
+use super::error::{ErrorCode, ScrapeError};
+use super::schema::ScrapeResults;
+
+use super::utils;
+
+/// Extract `ScrapeResults` from an HTML document without a browser.
+///
+/// `url` is the address the document was loaded from and is used to resolve links
+/// and the favicon. `text` is the text content of the first `text_selector` match
+/// (`html` when omitted).
+pub fn extract(
+    document: &str,
+    url: &str,
+    selectors: &[&str],
+    text_selector: Option<&str>,
+) -> Result<ScrapeResults, ScrapeError> {
+    let text_sel = parse_selector(text_selector.unwrap_or("html"))?;
+    let text = Html::parse_document(document)
+        .select(&text_sel)
+        .next()
+        .map(|elem| elem.text().collect::<String>())
+        .unwrap_or_default();
+    extract_with_text(document.to_string(), url.to_string(), text, selectors)
+}
+
+/// Same as `extract`, but with the page text supplied by the caller
+/// (e.g. the rendered `innerText` from the browser).
+pub fn extract_with_text(
+    document: String,
+    url: String,
+    text: String,
+    selectors: &[&str],
+) -> Result<ScrapeResults, ScrapeError> {
+    // parse ready
+    let fragments = Html::parse_document(&document);
+    let base_url = document_base(&fragments, &url);
+
+    // selectors
+    let links_selector = Selector::parse("a[href]").unwrap();
+    let favicon_selector = Selector::parse(r#"link[rel="icon"]"#).unwrap();
+    let title_selector = Selector::parse("title").unwrap();
+    let lang_selector = Selector::parse("html").unwrap();
+
+    let mut links: Vec<String> = fragments.select(&links_selector)
+        .filter_map(|elem| elem.value().attr("href"))
+        .filter_map(|href| utils::resolve_link(&base_url, href))
+        .collect();
+
+    let favicon: Option<String> = fragments.select(&favicon_selector)
+        .filter_map(|elem| elem.value().attr("href"))
+        .filter_map(|href| utils::resolve_link(&base_url, href))
+        .next();
+
+    let title: Option<String> = fragments.select(&title_selector)
+        .map(|elem| elem.inner_html())
+        .next();
+
+    let lang: Option<String> = fragments.select(&lang_selector)
+        .filter_map(|elem| elem.value().attr("lang").map(|s| s.to_string()))
+        .next();
+
+    let mut contents: HashMap<String, Vec<String>> = HashMap::new();
+    for s in selectors {
+        let sel = parse_selector(s)?;
+        let texts: Vec<String> = fragments.select(&sel)
+            .map(|elem| elem.text().collect::<String>().trim().to_string())
+            .collect();
+
+        contents.insert(s.to_string(), texts);
+    }
+
+    links.sort();
+    links.dedup();
+
+    Ok(ScrapeResults {
+        url,
+        title,
+        contents,
+        lang,
+        favicon,
+        links,
+        document,
+        text,
+        status: None,
+        headers: BTreeMap::new(),
+        mime_type: None,
+        redirects: Vec::new(),
+        partial: false,
+        actions: Vec::new(),
+        scroll: None,
+    })
+}
+
+/// Base URL for relative links: the first `<base href>`, resolved against the
+/// document URL, or the document URL itself.
+pub fn document_base(fragments: &Html, url: &str) -> String {
+    let base_selector = Selector::parse("base[href]").unwrap();
+    fragments.select(&base_selector)
+        .filter_map(|elem| elem.value().attr("href"))
+        .filter_map(|href| utils::resolve_link(url, href))
+        .next()
+        .unwrap_or_else(|| url.to_string())
+}
+
+pub(crate) fn parse_selector(selector: &str) -> Result<Selector, ScrapeError> {
+    Selector::parse(selector)
+        .map_err(|e| ScrapeError::new(ErrorCode::InvalidSelector, format!("invalid selector '{}': {}", selector, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOC: &str = r#"<html lang="en"><head>
+        <title>Fixture</title>
+        <link rel="icon" href="/favicon.ico">
+        </head><body>
+        <main><h1>Heading</h1><p class="lead"> lead text </p></main>
+        <a href="/b">b</a><a href="https://example.org/a">a</a>
+        <a href="c#part">c</a><a href="mailto:info@example.com">mail</a><a href="javascript:void(0)">js</a>
+        </body></html>"#;
+
+    #[test]
+    fn extract_fixture() {
+        let res = extract(DOC, "https://example.com/page", &["h1", ".lead"], Some("main")).unwrap();
+        assert_eq!(res.title.as_deref(), Some("Fixture"));
+        assert_eq!(res.lang.as_deref(), Some("en"));
+        assert_eq!(res.favicon.as_deref(), Some("https://example.com/favicon.ico"));
+        assert_eq!(res.links, vec!["https://example.com/b", "https://example.com/c", "https://example.org/a"]);
+        assert_eq!(res.contents["h1"], vec!["Heading"]);
+        assert_eq!(res.contents[".lead"], vec!["lead text"]);
+        assert_eq!(res.text, "Heading lead text ");
+    }
+
+    #[test]
+    fn base_href() {
+        let doc = r#"<html><head><base href="/docs/v2/"></head><body>
+            <a href="intro">intro</a><a href="../v1/">v1</a></body></html>"#;
+        let res = extract(doc, "https://example.com/page/index.html", &[], None).unwrap();
+        assert_eq!(res.links, vec!["https://example.com/docs/v1/", "https://example.com/docs/v2/intro"]);
+
+        let res = extract(DOC, "https://example.com/dir/page", &[], None).unwrap();
+        assert!(res.links.contains(&"https://example.com/dir/c".to_string()));
+    }
+
+    #[test]
+    fn invalid_selector() {
+        let err = extract(DOC, "https://example.com/", &["a["], None).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidSelector);
+        assert!(extract(DOC, "https://example.com/", &[], Some("::")).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+use std::{collections::BTreeMap, error::Error, time::Duration};
+
+use scraper::{Html, Selector};
+
+use super::error::{ErrorCode, ScrapeError};
+use super::network::DocumentResponse;
+use super::proxy::{ProxyClients, ProxyConfig};
+use super::schema::Redirect;
+use super::utils;
+
+/// Redirects followed before giving up
+const MAX_REDIRECTS: usize = 10;
+
+/// Static HTML fetch backend.
+///
+/// Downloads the document with a plain HTTP GET, without launching Chromium.
+/// Suitable for server-rendered pages.
+pub struct Fetcher {
+    user_agent: String,
+    client: reqwest::Client,
+    /// clients for the proxies used recently
+    proxied: ProxyClients,
+}
+
+/// Document downloaded by the `Fetcher`
+pub struct FetchedPage {
+    /// final URL after redirects
+    pub url: String,
+    pub status: u16,
+    pub document: String,
+    /// status, headers and redirect chain of the document
+    pub response: DocumentResponse,
+}
+
+impl FetchedPage {
+    /// Whether `selector` matches anything in the static document.
+    pub fn contains(&self, selector: &str) -> bool {
+        match Selector::parse(selector) {
+            Ok(sel) => Html::parse_document(&self.document).select(&sel).next().is_some(),
+            Err(_) => false,
+        }
+    }
+}
+
+impl Fetcher {
+    pub fn new(user_agent: &str) -> Result<Self, Box<dyn Error>> {
+        let client = Self::build_client(user_agent, None)?;
+        Ok(Fetcher { user_agent: user_agent.to_string(), client, proxied: ProxyClients::new() })
+    }
+
+    fn build_client(user_agent: &str, proxy: Option<&ProxyConfig>) -> Result<reqwest::Client, reqwest::Error> {
+        let mut builder = reqwest::Client::builder()
+            .user_agent(user_agent)
+            .timeout(Duration::from_secs(30))
+            // redirects are followed by hand to record the chain
+            .redirect(reqwest::redirect::Policy::none());
+        if let Some(proxy) = proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy.url())?);
+        }
+        builder.build()
+    }
+
+    fn client(&self, proxy: Option<&ProxyConfig>) -> Result<reqwest::Client, ScrapeError> {
+        match proxy {
+            Some(proxy) => self.proxied.get(proxy, || Self::build_client(&self.user_agent, Some(proxy))),
+            None => Ok(self.client.clone()),
+        }
+    }
+
+    /// Download `url`, through `proxy` when given; `timeout` bounds every request of the redirect chain.
+    ///
+    /// With a proxy, failing to connect is reported as `ProxyFailed`.
+    pub async fn fetch(&self, url: &str, timeout: Duration, proxy: Option<&ProxyConfig>) -> Result<FetchedPage, ScrapeError> {
+        let client = self.client(proxy)?;
+        let mut url = url.to_string();
+        let mut redirects: Vec<Redirect> = Vec::new();
+        let resp = loop {
+            let resp = client
+                .get(&url)
+                .header(reqwest::header::ACCEPT, "text/html,application/xhtml+xml;q=0.9,*/*;q=0.8")
+                .timeout(timeout)
+                .send()
+                .await
+                .map_err(|e| match proxy {
+                    Some(proxy) if e.is_connect() => {
+                        ScrapeError::new(ErrorCode::ProxyFailed, format!("proxy {} failed: {}", proxy, e))
+                    }
+                    _ => ScrapeError::from(e),
+                })?;
+            let location = resp
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_string());
+            let (true, Some(location)) = (resp.status().is_redirection(), location) else {
+                break resp;
+            };
+            if redirects.len() >= MAX_REDIRECTS {
+                return Err(ScrapeError::new(ErrorCode::DocumentFailed, format!("too many redirects: {}", url)));
+            }
+            let next = utils::url_resolve(&url, &location);
+            redirects.push(Redirect { url, status: resp.status().as_u16(), location: Some(location) });
+            url = next;
+        };
+
+        let url = resp.url().to_string();
+        let status = resp.status().as_u16();
+        let headers: BTreeMap<String, String> = resp
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| Some((name.as_str().to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        let content_type = headers.get("content-type").cloned().unwrap_or_default();
+        if !content_type.is_empty() && !content_type.contains("html") && !content_type.contains("xml") {
+            return Err(ScrapeError::new(ErrorCode::UnsupportedContent, format!("Content type is not HTML: {}", content_type)));
+        }
+        let mime_type = content_type.split(';').next().map(|m| m.trim().to_ascii_lowercase()).filter(|m| !m.is_empty());
+        let document = resp.text().await?;
+        let response = DocumentResponse {
+            url: Some(url.clone()),
+            status: Some(status),
+            headers,
+            mime_type,
+            redirects,
+            failure: None,
+        };
+        Ok(FetchedPage { url, status, document, response })
+    }
+}
@@ -0,0 +1,270 @@
+use std::error::Error;
+
+use chromiumoxide::Page;
+use chromiumoxide::cdp::browser_protocol::fetch::{
+    AuthChallengeResponse, AuthChallengeResponseResponse, AuthChallengeSource, ContinueRequestParams, ContinueWithAuthParams, EnableParams,
+    EventAuthRequired, EventRequestPaused, FailRequestParams, RequestPattern, RequestStage,
+};
+use chromiumoxide::cdp::browser_protocol::network::{ErrorReason, ResourceType};
+use futures::StreamExt;
+use regex::Regex;
+use tokio::task::AbortHandle;
+
+use super::error::ScrapeError;
+use super::proxy::ProxyConfig;
+use super::schema::{BlockRules, ResourceKind};
+use super::utils;
+
+/// Ad and tracker domains blocked by `block_trackers` (subdomains included)
+const TRACKER_DOMAINS: &[&str] = &[
+    "doubleclick.net",
+    "googlesyndication.com",
+    "googleadservices.com",
+    "google-analytics.com",
+    "googletagmanager.com",
+    "googletagservices.com",
+    "adservice.google.com",
+    "connect.facebook.net",
+    "amazon-adsystem.com",
+    "adnxs.com",
+    "criteo.com",
+    "criteo.net",
+    "taboola.com",
+    "outbrain.com",
+    "scorecardresearch.com",
+    "quantserve.com",
+    "hotjar.com",
+    "clarity.ms",
+    "bat.bing.com",
+    "ads-twitter.com",
+    "analytics.twitter.com",
+    "static.ads-twitter.com",
+    "mc.yandex.ru",
+    "pubmatic.com",
+    "rubiconproject.com",
+    "openx.net",
+    "moatads.com",
+    "adsrvr.org",
+    "casalemedia.com",
+    "smartadserver.com",
+    "yieldmo.com",
+    "segment.io",
+    "cdn.segment.com",
+    "mixpanel.com",
+    "nr-data.net",
+    "i-mobile.co.jp",
+    "microad.jp",
+    "logly.co.jp",
+    "ad-stir.com",
+    "impact-ad.jp",
+    "yjtag.yahoo.co.jp",
+    "ads.yahoo.co.jp",
+];
+
+/// `BlockRules` compiled for matching
+#[derive(Debug)]
+pub struct BlockList {
+    kinds: Vec<ResourceKind>,
+    urls: Vec<Regex>,
+    domains: Vec<String>,
+    trackers: bool,
+}
+
+impl BlockList {
+    /// Compile the rules; an invalid URL pattern is an `invalid_request`.
+    pub fn new(rules: &BlockRules) -> Result<Self, ScrapeError> {
+        let urls = rules
+            .block_urls
+            .iter()
+            .map(|pattern| {
+                url_pattern(pattern).map_err(|e| ScrapeError::invalid_request(format!("Invalid block_urls pattern '{}': {}", pattern, e)))
+            })
+            .collect::<Result<_, _>>()?;
+        let domains = rules
+            .block_domains
+            .iter()
+            .map(|domain| domain.trim().trim_start_matches("*.").trim_start_matches('.').to_ascii_lowercase())
+            .filter(|domain| !domain.is_empty())
+            .collect();
+        Ok(BlockList { kinds: rules.block_resources.clone(), urls, domains, trackers: rules.block_trackers })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.kinds.is_empty() && self.urls.is_empty() && self.domains.is_empty() && !self.trackers
+    }
+
+    /// Whether a subresource request should be failed
+    pub fn blocks(&self, url: &str, kind: ResourceKind) -> bool {
+        if self.kinds.contains(&kind) || self.urls.iter().any(|pattern| pattern.is_match(url)) {
+            return true;
+        }
+        let Some(host) = utils::url_host(url) else {
+            return false;
+        };
+        self.domains.iter().any(|domain| on_domain(&host, domain))
+            || (self.trackers && TRACKER_DOMAINS.iter().any(|domain| on_domain(&host, domain)))
+    }
+}
+
+/// `host` is `domain` or one of its subdomains
+fn on_domain(host: &str, domain: &str) -> bool {
+    host == domain || host.strip_suffix(domain).is_some_and(|rest| rest.ends_with('.'))
+}
+
+/// `/regex/` as is, anything else as a glob where `*` matches any characters
+fn url_pattern(pattern: &str) -> Result<Regex, regex::Error> {
+    if let Some(regex) = pattern.strip_prefix('/').and_then(|p| p.strip_suffix('/')).filter(|p| !p.is_empty()) {
+        return Regex::new(regex);
+    }
+    let glob = pattern.split('*').map(regex::escape).collect::<Vec<_>>().join(".*");
+    Regex::new(&format!("^{}$", glob))
+}
+
+fn resource_kind(resource_type: &ResourceType) -> ResourceKind {
+    match resource_type {
+        ResourceType::Document => ResourceKind::Document,
+        ResourceType::Stylesheet => ResourceKind::Stylesheet,
+        ResourceType::Image => ResourceKind::Image,
+        ResourceType::Media => ResourceKind::Media,
+        ResourceType::Font => ResourceKind::Font,
+        ResourceType::Script => ResourceKind::Script,
+        ResourceType::Xhr => ResourceKind::Xhr,
+        ResourceType::Fetch => ResourceKind::Fetch,
+        _ => ResourceKind::Other,
+    }
+}
+
+/// Intercepts the requests of a page with the Fetch domain: fails the blocked ones and
+/// answers the authentication challenges of the request's proxy with its credentials.
+///
+/// Chromium cannot be given proxy credentials on the command line, so they never leave
+/// the engine: the browser asks for them through `Fetch.authRequired` on every 407.
+/// Interception stops when it is dropped.
+pub struct RequestInterceptor {
+    task: AbortHandle,
+}
+
+impl RequestInterceptor {
+    /// Start intercepting, or do nothing when there are no rules and no proxy credentials.
+    pub async fn start(page: &Page, list: BlockList, proxy: Option<&ProxyConfig>) -> Result<Option<Self>, Box<dyn Error>> {
+        let credentials = proxy
+            .filter(|proxy| proxy.has_credentials())
+            .map(|proxy| (proxy.username.clone().unwrap_or_default(), proxy.password.clone().unwrap_or_default()));
+        if list.is_empty() && credentials.is_none() {
+            return Ok(None);
+        }
+        let main_frame = page.mainframe().await?;
+        let mut paused = page.event_listener::<EventRequestPaused>().await?;
+        let mut challenges = page.event_listener::<EventAuthRequired>().await?;
+        page.execute(
+            EnableParams::builder()
+                .pattern(RequestPattern::builder().url_pattern("*").request_stage(RequestStage::Request).build())
+                .handle_auth_requests(credentials.is_some())
+                .build(),
+        ).await?;
+
+        let page = page.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    Some(event) = paused.next() => {
+                        let kind = resource_kind(&event.resource_type);
+                        // never block the page itself
+                        let top_level = kind == ResourceKind::Document && main_frame.as_ref().is_none_or(|frame| *frame == event.frame_id);
+                        let blocked = !top_level && list.blocks(&event.request.url, kind);
+                        let page = page.clone();
+                        let id = event.request_id.clone();
+                        // answer without waiting, so one slow reply does not hold up the others
+                        tokio::spawn(async move {
+                            if blocked {
+                                let _ = page.execute(FailRequestParams::new(id, ErrorReason::BlockedByClient)).await;
+                            } else {
+                                let _ = page.execute(ContinueRequestParams::new(id)).await;
+                            }
+                        });
+                    }
+                    Some(event) = challenges.next() => {
+                        let response = auth_response(&event.auth_challenge.source, credentials.as_ref());
+                        let page = page.clone();
+                        tokio::spawn(async move {
+                            let _ = page.execute(ContinueWithAuthParams::new(event.request_id.clone(), response)).await;
+                        });
+                    }
+                    else => break,
+                }
+            }
+        });
+        Ok(Some(RequestInterceptor { task: task.abort_handle() }))
+    }
+}
+
+/// Credentials only go to the proxy; a site asking for them gets the browser's default answer.
+fn auth_response(source: &Option<AuthChallengeSource>, credentials: Option<&(String, String)>) -> AuthChallengeResponse {
+    match (source, credentials) {
+        (Some(AuthChallengeSource::Proxy), Some((username, password))) => AuthChallengeResponse {
+            response: AuthChallengeResponseResponse::ProvideCredentials,
+            username: Some(username.clone()),
+            password: Some(password.clone()),
+        },
+        _ => AuthChallengeResponse::new(AuthChallengeResponseResponse::Default),
+    }
+}
+
+impl Drop for RequestInterceptor {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(rules: BlockRules) -> BlockList {
+        BlockList::new(&rules).unwrap()
+    }
+
+    #[test]
+    fn resource_types_and_patterns() {
+        let l = list(BlockRules {
+            block_resources: vec![ResourceKind::Image, ResourceKind::Font],
+            block_urls: vec!["*.gif".to_string(), "/\\/ads?\\//".to_string()],
+            ..Default::default()
+        });
+        assert!(l.blocks("https://example.com/a.png", ResourceKind::Image));
+        assert!(!l.blocks("https://example.com/a.js", ResourceKind::Script));
+        assert!(l.blocks("https://example.com/spinner.gif", ResourceKind::Other));
+        assert!(!l.blocks("https://example.com/spinner.gif?v=2", ResourceKind::Other));
+        assert!(l.blocks("https://example.com/ad/banner.js", ResourceKind::Script));
+        assert!(!l.blocks("https://example.com/adventure.js", ResourceKind::Script));
+
+        assert!(BlockList::new(&BlockRules { block_urls: vec!["/(/".to_string()], ..Default::default() }).is_err());
+    }
+
+    #[test]
+    fn domains_and_trackers() {
+        let l = list(BlockRules { block_domains: vec!["*.cdn.example".to_string()], block_trackers: true, ..Default::default() });
+        assert!(l.blocks("https://cdn.example/x.js", ResourceKind::Script));
+        assert!(l.blocks("https://img.cdn.example/x.png", ResourceKind::Image));
+        assert!(!l.blocks("https://mycdn.example/x.js", ResourceKind::Script));
+        assert!(l.blocks("https://www.google-analytics.com/analytics.js", ResourceKind::Script));
+        assert!(l.blocks("https://securepubads.g.doubleclick.net/tag/js/gpt.js", ResourceKind::Script));
+        assert!(!l.blocks("https://www.google.com/", ResourceKind::Document));
+
+        let none = list(BlockRules::default());
+        assert!(!none.blocks("https://www.google-analytics.com/analytics.js", ResourceKind::Script));
+    }
+
+    #[test]
+    fn credentials_only_for_the_proxy() {
+        let credentials = ("user".to_string(), "pass".to_string());
+        let proxy = auth_response(&Some(AuthChallengeSource::Proxy), Some(&credentials));
+        assert_eq!(proxy.response, AuthChallengeResponseResponse::ProvideCredentials);
+        assert_eq!((proxy.username.as_deref(), proxy.password.as_deref()), (Some("user"), Some("pass")));
+
+        for response in [auth_response(&Some(AuthChallengeSource::Server), Some(&credentials)), auth_response(&None, Some(&credentials)), auth_response(&Some(AuthChallengeSource::Proxy), None)] {
+            assert_eq!(response.response, AuthChallengeResponseResponse::Default);
+            assert_eq!(response.username, None);
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use tokio::task::AbortHandle;
+
+use super::browser::Engine;
+use super::crawl;
+use super::error::{ErrorCode, ScrapeError};
+use super::schema::{JobInfo, JobRequest, JobStatus, ScraperResult};
+
+/// Job store configuration
+#[derive(Debug, Clone)]
+pub struct JobConfig {
+    /// Maximum number of jobs kept in memory (running and finished)
+    pub max_jobs: usize,
+    /// How long a finished job stays available for polling
+    pub ttl: Duration,
+}
+
+impl Default for JobConfig {
+    fn default() -> Self {
+        JobConfig {
+            max_jobs: 1000,
+            ttl: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+struct Job {
+    info: JobInfo,
+    handle: Option<AbortHandle>,
+    finished: Option<Instant>,
+}
+
+/// Bounded in-memory store of asynchronous jobs.
+///
+/// Every submitted job runs in its own task. Finished jobs are dropped after `ttl`;
+/// when the store is full the oldest finished job is evicted, and submissions are
+/// rejected if every slot is taken by a running job.
+pub struct JobStore {
+    config: JobConfig,
+    jobs: Mutex<HashMap<String, Job>>,
+}
+
+impl JobStore {
+    pub fn new(config: JobConfig) -> Arc<Self> {
+        Arc::new(JobStore {
+            config,
+            jobs: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Start a job and return its initial state.
+    pub fn submit(self: &Arc<Self>, engine: Arc<Engine>, req: JobRequest) -> Result<JobInfo, ScrapeError> {
+        let info = self.reserve()?;
+        let id = info.id.clone();
+
+        let store = self.clone();
+        let job_id = id.clone();
+        let handle = tokio::spawn(async move {
+            store.update(&job_id, |info| info.status = JobStatus::Running);
+            let outcome = store.run(&engine, &job_id, req).await;
+            store.finish(&job_id, outcome);
+        });
+
+        if let Some(job) = self.jobs.lock().unwrap().get_mut(&id) {
+            job.handle = Some(handle.abort_handle());
+        }
+        Ok(info)
+    }
+
+    /// Take a slot for a new queued job, evicting the oldest finished job if the store is full.
+    fn reserve(&self) -> Result<JobInfo, ScrapeError> {
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let info = JobInfo {
+            id: id.clone(),
+            status: JobStatus::Queued,
+            pages_done: 0,
+            created_at: unix_millis(),
+            finished_at: None,
+            result: None,
+            error: None,
+            error_code: None,
+        };
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            self.purge(&mut jobs);
+            if jobs.len() >= self.config.max_jobs {
+                let oldest = jobs
+                    .iter()
+                    .filter_map(|(id, job)| job.finished.map(|t| (id.clone(), t)))
+                    .min_by_key(|(_, t)| *t)
+                    .map(|(id, _)| id);
+                match oldest {
+                    Some(oldest) => {
+                        jobs.remove(&oldest);
+                    }
+                    None => return Err(ScrapeError::new(ErrorCode::Busy, "job store is full")),
+                }
+            }
+            jobs.insert(id, Job { info: info.clone(), handle: None, finished: None });
+        }
+        Ok(info)
+    }
+
+    pub fn get(&self, id: &str) -> Option<JobInfo> {
+        let mut jobs = self.jobs.lock().unwrap();
+        self.purge(&mut jobs);
+        jobs.get(id).map(|job| job.info.clone())
+    }
+
+    /// Cancel a job. Aborting the task drops its page leases, which closes the tabs.
+    pub fn cancel(&self, id: &str) -> Option<JobInfo> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let job = jobs.get_mut(id)?;
+        if !job.info.status.is_finished() {
+            if let Some(handle) = job.handle.take() {
+                handle.abort();
+            }
+            job.info.status = JobStatus::Cancelled;
+            job.info.finished_at = Some(unix_millis());
+            job.finished = Some(Instant::now());
+        }
+        Some(job.info.clone())
+    }
+
+    async fn run(self: &Arc<Self>, engine: &Engine, id: &str, req: JobRequest) -> Result<serde_json::Value, ScrapeError> {
+        let value = match req {
+            JobRequest::Scrape(req) => {
+                let selectors = req.selectors.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
+                let results = engine
+                    .scraping(&req.url, selectors, req.text_selector.as_deref(), req.waiting_selector.as_deref(), req.render, &req.options)
+                    .await?;
+                self.update(id, |info| info.pages_done = 1);
+                serde_json::to_value(ScraperResult::Success { status: results.status.unwrap_or(200), url: req.url, results })
+            }
+            JobRequest::Spec(spec) => {
+                let results = engine.scrape_spec(&spec).await?;
+                self.update(id, |info| info.pages_done = 1);
+                serde_json::to_value(results)
+            }
+            JobRequest::Crawl(req) => {
+                let results = crawl::crawl_with(engine, &req, |_| {
+                    self.update(id, |info| info.pages_done += 1);
+                    true
+                })
+                .await?;
+                serde_json::to_value(results)
+            }
+        };
+        Ok(value?)
+    }
+
+    /// Record the outcome of a job run.
+    fn finish(&self, id: &str, outcome: Result<serde_json::Value, ScrapeError>) {
+        self.update(id, |info| {
+            // a cancelled job keeps its state
+            if info.status.is_finished() {
+                return;
+            }
+            match outcome {
+                Ok(result) => {
+                    info.status = JobStatus::Succeeded;
+                    info.result = Some(result);
+                }
+                Err(e) => {
+                    info.status = JobStatus::Failed;
+                    info.error = Some(e.message);
+                    info.error_code = Some(e.code);
+                }
+            }
+            info.finished_at = Some(unix_millis());
+        });
+    }
+
+    fn update(&self, id: &str, f: impl FnOnce(&mut JobInfo)) {
+        if let Some(job) = self.jobs.lock().unwrap().get_mut(id) {
+            f(&mut job.info);
+            if job.info.status.is_finished() && job.finished.is_none() {
+                job.finished = Some(Instant::now());
+                job.handle = None;
+            }
+        }
+    }
+
+    /// Drop finished jobs older than the TTL.
+    fn purge(&self, jobs: &mut HashMap<String, Job>) {
+        let ttl = self.config.ttl;
+        jobs.retain(|_, job| job.finished.is_none_or(|t| t.elapsed() < ttl));
+    }
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(max_jobs: usize, ttl: Duration) -> Arc<JobStore> {
+        JobStore::new(JobConfig { max_jobs, ttl })
+    }
+
+    fn succeed(store: &JobStore, id: &str) {
+        store.finish(id, Ok(serde_json::Value::Null));
+    }
+
+    #[test]
+    fn full_store_evicts_oldest_finished() {
+        let store = store(3, Duration::from_secs(60));
+        let first = store.reserve().unwrap().id;
+        let second = store.reserve().unwrap().id;
+        let running = store.reserve().unwrap().id;
+        succeed(&store, &first);
+        std::thread::sleep(Duration::from_millis(5));
+        succeed(&store, &second);
+
+        let newest = store.reserve().unwrap().id;
+        assert!(store.get(&first).is_none());
+        assert_eq!(store.get(&second).unwrap().status, JobStatus::Succeeded);
+        assert_eq!(store.get(&running).unwrap().status, JobStatus::Queued);
+        assert_eq!(store.get(&newest).unwrap().status, JobStatus::Queued);
+    }
+
+    #[test]
+    fn busy_when_every_slot_is_running() {
+        let store = store(2, Duration::from_secs(60));
+        let a = store.reserve().unwrap().id;
+        let b = store.reserve().unwrap().id;
+        store.update(&a, |info| info.status = JobStatus::Running);
+        store.update(&b, |info| info.status = JobStatus::Running);
+
+        let err = store.reserve().unwrap_err();
+        assert_eq!(err.code, ErrorCode::Busy);
+
+        succeed(&store, &a);
+        assert!(store.reserve().is_ok());
+    }
+
+    #[test]
+    fn finished_jobs_expire_after_ttl() {
+        let store = store(10, Duration::from_millis(20));
+        let done = store.reserve().unwrap().id;
+        let running = store.reserve().unwrap().id;
+        succeed(&store, &done);
+        assert!(store.get(&done).is_some());
+
+        std::thread::sleep(Duration::from_millis(40));
+        assert!(store.get(&done).is_none());
+        assert!(store.get(&running).is_some());
+    }
+
+    #[test]
+    fn cancelled_job_stays_cancelled() {
+        let store = store(10, Duration::from_secs(60));
+        let id = store.reserve().unwrap().id;
+        store.update(&id, |info| info.status = JobStatus::Running);
+
+        let info = store.cancel(&id).unwrap();
+        assert_eq!(info.status, JobStatus::Cancelled);
+        assert!(info.finished_at.is_some());
+
+        // a run that completes after the cancel does not overwrite it
+        store.finish(&id, Err(ScrapeError::new(ErrorCode::NavigationTimeout, "late")));
+        let info = store.get(&id).unwrap();
+        assert_eq!(info.status, JobStatus::Cancelled);
+        assert!(info.error.is_none());
+        assert!(store.cancel("missing").is_none());
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chromiumoxide::Page;
+use chromiumoxide::cdp::browser_protocol::network::{EventLoadingFailed, EventLoadingFinished, EventRequestWillBeSent, EventResponseReceived, Headers, ResourceType};
+use futures::StreamExt;
+use tokio::task::AbortHandle;
+
+pub use super::error::DocumentError;
+use super::schema::{Redirect, ScrapeResults};
+
+/// Network response of the main document
+#[derive(Debug, Clone, Default)]
+pub struct DocumentResponse {
+    /// final URL after redirects
+    pub url: Option<String>,
+    pub status: Option<u16>,
+    pub headers: BTreeMap<String, String>,
+    pub mime_type: Option<String>,
+    pub redirects: Vec<Redirect>,
+    /// set when the document request itself failed
+    pub failure: Option<String>,
+}
+
+impl DocumentResponse {
+    /// Copy the response details into scrape results.
+    pub fn fill(self, results: &mut ScrapeResults) {
+        results.status = self.status;
+        results.headers = self.headers;
+        results.mime_type = self.mime_type;
+        results.redirects = self.redirects;
+    }
+}
+
+/// Follows the main-frame document request through redirects and
+/// script-initiated navigations.
+#[derive(Debug, Default)]
+struct Tracker {
+    main_frame: Option<String>,
+    request_id: Option<String>,
+    /// URL of the request currently tracked
+    url: String,
+    response: DocumentResponse,
+    /// requests of any type that have not finished yet
+    inflight: HashSet<String>,
+}
+
+impl Tracker {
+    fn on_request(&mut self, id: &str, url: &str, frame: Option<&str>, is_document: bool, redirect: Option<Redirect>) {
+        self.inflight.insert(id.to_string());
+        if !is_document || (self.main_frame.is_some() && frame != self.main_frame.as_deref()) {
+            return;
+        }
+        // without a known main frame only the first document is followed
+        if self.main_frame.is_none() && self.request_id.as_deref().is_some_and(|current| current != id) {
+            return;
+        }
+        match self.request_id.as_deref() {
+            // HTTP redirect: same request id, the previous hop comes with the event
+            Some(current) if current == id => {
+                if let Some(redirect) = redirect {
+                    self.response.redirects.push(redirect);
+                }
+            }
+            // a new navigation in the main frame once the first one answered (JS / meta refresh)
+            Some(_) => {
+                let Some(status) = self.response.status else {
+                    return;
+                };
+                self.response.redirects.push(Redirect {
+                    url: self.url.clone(),
+                    status,
+                    location: Some(url.to_string()),
+                });
+                self.response.status = None;
+                self.response.headers.clear();
+                self.response.mime_type = None;
+                self.response.failure = None;
+            }
+            None => {}
+        }
+        self.request_id = Some(id.to_string());
+        self.url = url.to_string();
+    }
+
+    fn on_response(&mut self, id: &str, url: &str, status: u16, headers: BTreeMap<String, String>, mime_type: &str) {
+        if self.request_id.as_deref() != Some(id) {
+            return;
+        }
+        self.response.url = Some(url.to_string());
+        self.response.status = Some(status);
+        self.response.headers = headers;
+        self.response.mime_type = if mime_type.is_empty() { None } else { Some(mime_type.to_string()) };
+    }
+
+    fn on_finished(&mut self, id: &str) {
+        self.inflight.remove(id);
+    }
+
+    fn on_failed(&mut self, id: &str, error_text: &str) {
+        self.inflight.remove(id);
+        if self.request_id.as_deref() != Some(id) || self.response.status.is_some() {
+            return;
+        }
+        self.response.failure = Some(error_text.to_string());
+    }
+}
+
+enum NetEvent {
+    Request(Arc<EventRequestWillBeSent>),
+    Response(Arc<EventResponseReceived>),
+    Finished(Arc<EventLoadingFinished>),
+    Failed(Arc<EventLoadingFailed>),
+}
+
+/// Records the main document response of the next navigation of a page
+/// and keeps counting the requests in flight until it is dropped.
+///
+/// Start it before `goto` and read the `document` once the navigation returned.
+pub struct NavigationRecorder {
+    tracker: Arc<Mutex<Tracker>>,
+    task: AbortHandle,
+}
+
+impl NavigationRecorder {
+    pub async fn start(page: &Page) -> Result<Self, Box<dyn Error>> {
+        let main_frame = page.mainframe().await?.map(|frame| frame.inner().clone());
+        let requests = page.event_listener::<EventRequestWillBeSent>().await?.map(NetEvent::Request);
+        let responses = page.event_listener::<EventResponseReceived>().await?.map(NetEvent::Response);
+        let finished = page.event_listener::<EventLoadingFinished>().await?.map(NetEvent::Finished);
+        let failures = page.event_listener::<EventLoadingFailed>().await?.map(NetEvent::Failed);
+        let mut events = futures::stream::select(
+            futures::stream::select(requests, responses),
+            futures::stream::select(finished, failures),
+        );
+
+        let tracker = Arc::new(Mutex::new(Tracker { main_frame, ..Default::default() }));
+        let state = tracker.clone();
+        let task = tokio::spawn(async move {
+            while let Some(event) = events.next().await {
+                let mut tracker = state.lock().unwrap();
+                match event {
+                    NetEvent::Request(e) => {
+                        let redirect = e.redirect_response.as_ref().map(|r| Redirect {
+                            url: r.url.clone(),
+                            status: r.status as u16,
+                            location: header_map(&r.headers).remove("location"),
+                        });
+                        let is_document = e.r#type == Some(ResourceType::Document);
+                        let frame = e.frame_id.as_ref().map(|f| f.inner().as_str());
+                        tracker.on_request(e.request_id.inner(), &e.request.url, frame, is_document, redirect);
+                    }
+                    NetEvent::Response(e) => {
+                        let r = &e.response;
+                        tracker.on_response(e.request_id.inner(), &r.url, r.status as u16, header_map(&r.headers), &r.mime_type);
+                    }
+                    NetEvent::Finished(e) => tracker.on_finished(e.request_id.inner()),
+                    NetEvent::Failed(e) => tracker.on_failed(e.request_id.inner(), &e.error_text),
+                }
+            }
+        });
+
+        Ok(NavigationRecorder { tracker, task: task.abort_handle() })
+    }
+
+    /// The main document response seen so far.
+    pub async fn document(&self) -> DocumentResponse {
+        // events are delivered by the handler task; give the last ones a moment to arrive
+        for _ in 0..10 {
+            {
+                let tracker = self.tracker.lock().unwrap();
+                if tracker.response.status.is_some() || tracker.response.failure.is_some() {
+                    break;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        self.tracker.lock().unwrap().response.clone()
+    }
+
+    /// Number of requests started and not finished yet
+    pub fn inflight(&self) -> usize {
+        self.tracker.lock().unwrap().inflight.len()
+    }
+}
+
+impl Drop for NavigationRecorder {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// CDP headers (a JSON object) with lowercase names.
+fn header_map(headers: &Headers) -> BTreeMap<String, String> {
+    headers
+        .inner()
+        .as_object()
+        .map(|object| {
+            object
+                .iter()
+                .map(|(name, value)| {
+                    let value = value.as_str().map(|s| s.to_string()).unwrap_or_else(|| value.to_string());
+                    (name.to_ascii_lowercase(), value)
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker() -> Tracker {
+        Tracker { main_frame: Some("main".to_string()), ..Default::default() }
+    }
+
+    #[test]
+    fn redirect_chain() {
+        let mut t = tracker();
+        t.on_request("1", "http://example.com/", Some("main"), true, None);
+        let hop = Redirect { url: "http://example.com/".to_string(), status: 301, location: Some("https://example.com/".to_string()) };
+        t.on_request("1", "https://example.com/", Some("main"), true, Some(hop.clone()));
+        // subresources and iframes are ignored
+        t.on_request("2", "https://example.com/app.js", Some("main"), false, None);
+        t.on_request("3", "https://ads.example/", Some("child"), true, None);
+        t.on_response("3", "https://ads.example/", 200, BTreeMap::new(), "text/html");
+
+        let headers = BTreeMap::from([("content-type".to_string(), "text/html".to_string())]);
+        t.on_response("1", "https://example.com/", 404, headers, "text/html");
+
+        assert_eq!(t.response.status, Some(404));
+        assert_eq!(t.response.url.as_deref(), Some("https://example.com/"));
+        assert_eq!(t.response.mime_type.as_deref(), Some("text/html"));
+        assert_eq!(t.response.redirects, vec![hop]);
+    }
+
+    #[test]
+    fn inflight_requests() {
+        let mut t = tracker();
+        t.on_request("1", "https://example.com/", Some("main"), true, None);
+        t.on_request("2", "https://example.com/app.js", Some("main"), false, None);
+        t.on_request("3", "https://example.com/poll", Some("main"), false, None);
+        // a redirect keeps the request id
+        t.on_request("1", "https://example.com/index.html", Some("main"), true, None);
+        assert_eq!(t.inflight.len(), 3);
+        t.on_finished("1");
+        t.on_failed("2", "net::ERR_ABORTED");
+        assert_eq!(t.inflight.len(), 1);
+        // the document already answered, so a failing subresource is not a document failure
+        assert_eq!(t.response.failure, None);
+    }
+
+    #[test]
+    fn script_navigation_and_failure() {
+        let mut t = tracker();
+        t.on_request("1", "https://example.com/a", Some("main"), true, None);
+        t.on_response("1", "https://example.com/a", 200, BTreeMap::new(), "text/html");
+        t.on_request("2", "https://example.com/b", Some("main"), true, None);
+        t.on_failed("2", "net::ERR_CONNECTION_REFUSED");
+
+        assert_eq!(t.response.redirects.len(), 1);
+        assert_eq!(t.response.redirects[0].location.as_deref(), Some("https://example.com/b"));
+        assert_eq!(t.response.status, None);
+        assert_eq!(t.response.failure.as_deref(), Some("net::ERR_CONNECTION_REFUSED"));
+    }
+
+    #[test]
+    fn headers_lowercase() {
+        let headers = Headers::new(serde_json::json!({ "Content-Type": "text/html", "X-Count": 2 }));
+        let map = header_map(&headers);
+        assert_eq!(map["content-type"], "text/html");
+        assert_eq!(map["x-count"], "2");
+    }
+}
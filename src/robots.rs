@@ -0,0 +1,379 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub use super::error::BlockedByRobots;
+use super::error::{ErrorCode, ScrapeError};
+use super::proxy::{ProxyClients, ProxyConfig};
+use super::utils;
+
+/// Product token matched against `User-agent` lines
+pub const ROBOTS_TOKEN: &str = "371tti";
+
+/// How long fetched rules are reused before robots.txt is requested again
+const CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Longest `Crawl-delay` honored; larger values are clamped to it
+pub const MAX_CRAWL_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+/// Rules of a robots.txt that apply to one user-agent token.
+#[derive(Debug, Clone, Default)]
+pub struct Robots {
+    rules: Vec<Rule>,
+    crawl_delay: Option<Duration>,
+}
+
+impl Robots {
+    /// Everything allowed (missing robots.txt)
+    pub fn allow_all() -> Self {
+        Robots::default()
+    }
+
+    /// Everything disallowed (robots.txt unreachable)
+    pub fn disallow_all() -> Self {
+        Robots {
+            rules: vec![Rule { allow: false, pattern: "/".to_string() }],
+            crawl_delay: None,
+        }
+    }
+
+    /// Parse robots.txt, keeping the group that matches `token`
+    /// (or the `*` group when no group names it).
+    pub fn parse(content: &str, token: &str) -> Self {
+        let token = token.to_ascii_lowercase();
+        let mut specific: Option<Robots> = None;
+        let mut wildcard: Option<Robots> = None;
+
+        // user-agents of the group being read, and whether its rules started
+        let mut agents: Vec<String> = Vec::new();
+        let mut in_rules = false;
+        let mut group = Robots::default();
+
+        let mut flush = |agents: &[String], group: &Robots| {
+            for agent in agents {
+                if agent == "*" {
+                    let target = wildcard.get_or_insert_with(Robots::default);
+                    target.merge(group);
+                } else if !agent.is_empty() && agent.eq_ignore_ascii_case(&token) {
+                    let target = specific.get_or_insert_with(Robots::default);
+                    target.merge(group);
+                }
+            }
+        };
+
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let key = key.trim().to_ascii_lowercase();
+            let value = value.trim();
+            match key.as_str() {
+                "user-agent" => {
+                    if in_rules {
+                        flush(&agents, &group);
+                        agents.clear();
+                        group = Robots::default();
+                        in_rules = false;
+                    }
+                    agents.push(value.to_ascii_lowercase());
+                }
+                "allow" | "disallow" => {
+                    in_rules = true;
+                    // an empty Disallow allows everything, same as no rule
+                    if !value.is_empty() {
+                        group.rules.push(Rule { allow: key == "allow", pattern: value.to_string() });
+                    }
+                }
+                "crawl-delay" => {
+                    in_rules = true;
+                    group.crawl_delay = value.parse::<f64>().ok()
+                        .filter(|d| d.is_finite() && *d >= 0.0)
+                        .map(|d| Duration::from_secs_f64(d.min(MAX_CRAWL_DELAY.as_secs_f64())));
+                }
+                _ => {}
+            }
+        }
+        flush(&agents, &group);
+
+        specific.or(wildcard).unwrap_or_default()
+    }
+
+    fn merge(&mut self, other: &Robots) {
+        self.rules.extend(other.rules.iter().cloned());
+        if other.crawl_delay.is_some() {
+            self.crawl_delay = other.crawl_delay;
+        }
+    }
+
+    /// Whether `path` (path and query of the URL) may be fetched.
+    /// The longest matching rule wins; on a tie Allow wins.
+    pub fn is_allowed(&self, path: &str) -> bool {
+        let mut best: Option<(usize, bool)> = None;
+        for rule in &self.rules {
+            if pattern_matches(&rule.pattern, path) {
+                let len = rule.pattern.len();
+                best = match best {
+                    Some((best_len, best_allow)) if best_len > len || (best_len == len && best_allow) => {
+                        Some((best_len, best_allow))
+                    }
+                    _ => Some((len, rule.allow)),
+                };
+            }
+        }
+        best.is_none_or(|(_, allow)| allow)
+    }
+
+    pub fn crawl_delay(&self) -> Option<Duration> {
+        self.crawl_delay
+    }
+}
+
+/// Match a robots.txt path pattern (`*` wildcard, trailing `$` anchor) against `path`.
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(p) => (p, true),
+        None => (pattern, false),
+    };
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let mut pos = 0;
+    for (i, part) in parts.iter().enumerate() {
+        if i == 0 {
+            if !path.starts_with(part) {
+                return false;
+            }
+            pos = part.len();
+        } else if i == parts.len() - 1 && anchored {
+            return path.len() >= pos + part.len() && path.ends_with(part);
+        } else {
+            match path[pos..].find(part) {
+                Some(found) => pos += found + part.len(),
+                None => return false,
+            }
+        }
+    }
+    !anchored || pos == path.len()
+}
+
+struct Entry {
+    robots: Arc<Robots>,
+    fetched: Instant,
+    /// next time a request to this origin may start (Crawl-delay)
+    next_slot: Instant,
+}
+
+/// Per-origin robots.txt cache that also spaces requests by `Crawl-delay`.
+pub struct RobotsCache {
+    user_agent: String,
+    client: reqwest::Client,
+    /// clients for the proxies used recently
+    proxied: ProxyClients,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl RobotsCache {
+    pub fn new(user_agent: &str) -> Result<Self, Box<dyn Error>> {
+        let client = Self::build_client(user_agent, None)?;
+        Ok(RobotsCache { user_agent: user_agent.to_string(), client, proxied: ProxyClients::new(), entries: Mutex::new(HashMap::new()) })
+    }
+
+    fn build_client(user_agent: &str, proxy: Option<&ProxyConfig>) -> Result<reqwest::Client, reqwest::Error> {
+        let mut builder = reqwest::Client::builder().user_agent(user_agent).timeout(Duration::from_secs(10));
+        if let Some(proxy) = proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy.url())?);
+        }
+        builder.build()
+    }
+
+    /// Check `url` against its origin's robots.txt and wait for the crawl delay.
+    /// robots.txt is fetched through `proxy`, the proxy of the request itself.
+    ///
+    /// Fails with `NavigationTimeout` instead of waiting when the crawl delay would only
+    /// let the request start after `deadline`, and with `ProxyFailed` (without caching
+    /// anything) when the proxy cannot be reached.
+    pub async fn check(&self, url: &str, deadline: Instant, proxy: Option<&ProxyConfig>) -> Result<(), ScrapeError> {
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return Ok(());
+        }
+        let origin = url.split('/').take(3).collect::<Vec<&str>>().join("/");
+        let robots = self.rules(&origin, proxy).await?;
+
+        let path = &url[origin.len()..];
+        let path = path.split('#').next().unwrap_or("");
+        let path = if path.is_empty() { "/" } else { path };
+        if !robots.is_allowed(&utils::url_decode(path)) && !robots.is_allowed(path) {
+            return Err(BlockedByRobots { url: url.to_string() }.into());
+        }
+
+        if let Some(delay) = robots.crawl_delay() {
+            let wait = {
+                let mut entries = self.entries.lock().unwrap();
+                let now = Instant::now();
+                match entries.get_mut(&origin) {
+                    Some(entry) => {
+                        let start = entry.next_slot.max(now);
+                        if start > deadline {
+                            return Err(ScrapeError::new(
+                                ErrorCode::NavigationTimeout,
+                                format!("crawl-delay of {} leaves no time before the request deadline", origin),
+                            ));
+                        }
+                        entry.next_slot = start + delay;
+                        start - now
+                    }
+                    None => Duration::ZERO,
+                }
+            };
+            tokio::time::sleep(wait).await;
+        }
+        Ok(())
+    }
+
+    async fn rules(&self, origin: &str, proxy: Option<&ProxyConfig>) -> Result<Arc<Robots>, ScrapeError> {
+        if let Some(entry) = self.entries.lock().unwrap().get(origin)
+            && entry.fetched.elapsed() < CACHE_TTL
+        {
+            return Ok(entry.robots.clone());
+        }
+
+        let robots = Arc::new(self.fetch(origin, proxy).await?);
+        let mut entries = self.entries.lock().unwrap();
+        let next_slot = entries.get(origin).map(|e| e.next_slot).unwrap_or_else(Instant::now);
+        entries.insert(origin.to_string(), Entry { robots: robots.clone(), fetched: Instant::now(), next_slot });
+        Ok(robots)
+    }
+
+    async fn fetch(&self, origin: &str, proxy: Option<&ProxyConfig>) -> Result<Robots, ScrapeError> {
+        let client = match proxy {
+            Some(proxy) => self.proxied.get(proxy, || Self::build_client(&self.user_agent, Some(proxy)))?,
+            None => self.client.clone(),
+        };
+        let resp = client.get(format!("{}/robots.txt", origin)).send().await;
+        // a dead proxy says nothing about the site
+        if let (Err(e), Some(proxy)) = (&resp, proxy)
+            && e.is_connect()
+        {
+            return Err(ScrapeError::new(ErrorCode::ProxyFailed, format!("proxy {} failed: {}", proxy, e)));
+        }
+        Ok(match resp {
+            Ok(resp) if resp.status().is_success() => match resp.text().await {
+                Ok(body) => Robots::parse(&body, ROBOTS_TOKEN),
+                Err(_) => Robots::disallow_all(),
+            },
+            // no robots.txt: no restrictions
+            Ok(resp) if resp.status().is_client_error() => Robots::allow_all(),
+            // server error or unreachable: assume complete disallow (RFC 9309)
+            _ => Robots::disallow_all(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOTS: &str = "
+User-agent: *
+Disallow: /private/
+Allow: /private/public
+Disallow: /*.pdf$
+Crawl-delay: 2
+
+User-agent: otherbot
+Disallow: /
+";
+
+    #[test]
+    fn wildcard_group() {
+        let r = Robots::parse(ROBOTS, ROBOTS_TOKEN);
+        assert!(r.is_allowed("/"));
+        assert!(!r.is_allowed("/private/x"));
+        assert!(r.is_allowed("/private/public/x"));
+        assert!(!r.is_allowed("/docs/a.pdf"));
+        assert!(r.is_allowed("/docs/a.pdf?download=1"));
+        assert_eq!(r.crawl_delay(), Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn specific_group_wins() {
+        let r = Robots::parse(ROBOTS, "otherbot");
+        assert!(!r.is_allowed("/"));
+        assert_eq!(r.crawl_delay(), None);
+
+        let r = Robots::parse("User-agent: 371tti\nDisallow: /a\n\nUser-agent: *\nDisallow: /", ROBOTS_TOKEN);
+        assert!(r.is_allowed("/b"));
+        assert!(!r.is_allowed("/a/b"));
+    }
+
+    #[test]
+    fn agents_match_exactly() {
+        // a token that is only part of ours, or an empty one, names some other crawler
+        for agent in ["tti", "371", ""] {
+            let r = Robots::parse(&format!("User-agent: {}\nDisallow: /", agent), ROBOTS_TOKEN);
+            assert!(r.is_allowed("/a"), "{:?} applied", agent);
+        }
+        let r = Robots::parse("User-agent: 371TTI\nDisallow: /", ROBOTS_TOKEN);
+        assert!(!r.is_allowed("/a"));
+    }
+
+    #[test]
+    fn crawl_delay_is_clamped() {
+        let r = Robots::parse("User-agent: *\nCrawl-delay: 86400", ROBOTS_TOKEN);
+        assert_eq!(r.crawl_delay(), Some(MAX_CRAWL_DELAY));
+    }
+
+    #[tokio::test]
+    async fn crawl_delay_past_deadline_fails() {
+        let cache = RobotsCache::new("test").unwrap();
+        let robots = Arc::new(Robots::parse("User-agent: *\nCrawl-delay: 10", ROBOTS_TOKEN));
+        let next_slot = Instant::now() + Duration::from_secs(10);
+        cache.entries.lock().unwrap().insert(
+            "http://example.com".to_string(),
+            Entry { robots, fetched: Instant::now(), next_slot },
+        );
+
+        let deadline = Instant::now() + Duration::from_secs(1);
+        let err = cache.check("http://example.com/a", deadline, None).await.unwrap_err();
+        assert_eq!(err.code, ErrorCode::NavigationTimeout);
+        // the slot is left for a request that can use it
+        assert_eq!(cache.entries.lock().unwrap()["http://example.com"].next_slot, next_slot);
+    }
+
+    #[tokio::test]
+    async fn dead_proxy_is_not_cached() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy = ProxyConfig::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        drop(listener);
+
+        let cache = RobotsCache::new("test").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let err = cache.check("http://example.com/a", deadline, Some(&proxy)).await.unwrap_err();
+        assert_eq!(err.code, ErrorCode::ProxyFailed);
+        assert!(cache.entries.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn patterns() {
+        assert!(pattern_matches("/a*b", "/a/x/b/c"));
+        assert!(pattern_matches("/a*b$", "/a/x/b"));
+        assert!(!pattern_matches("/a*b$", "/a/x/b/c"));
+        assert!(pattern_matches("/$", "/"));
+        assert!(!pattern_matches("/$", "/a"));
+        assert!(pattern_matches("*", "/anything"));
+    }
+
+    #[test]
+    fn empty_disallow_and_tie() {
+        let r = Robots::parse("User-agent: *\nDisallow:", ROBOTS_TOKEN);
+        assert!(r.is_allowed("/x"));
+        let r = Robots::parse("User-agent: *\nDisallow: /page\nAllow: /page", ROBOTS_TOKEN);
+        assert!(r.is_allowed("/page"));
+    }
+}
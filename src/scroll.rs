@@ -0,0 +1,166 @@
+use std::time::Duration;
+
+use chromiumoxide::Page;
+use serde::Deserialize;
+use tokio::time::Instant;
+
+use super::error::ScrapeError;
+use super::extract;
+use super::schema::{AutoScroll, ScrollReport, ScrollStop};
+
+/// Pause after each screen so intersection observers and lazy loaders can react
+const STEP_DELAY: Duration = Duration::from_millis(150);
+
+/// How often the page height is checked while waiting for it to grow
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Longest wait for lazy images to finish loading
+const IMAGE_WAIT: Duration = Duration::from_secs(5);
+
+/// Page state after a scroll
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct Measure {
+    height: u64,
+    bottom: bool,
+    count: Option<usize>,
+}
+
+/// Scroll one screen at a time until the page stops growing, a cap is hit or
+/// `until_selector` matches enough elements; then load lazy images and scroll back to the top.
+pub async fn expand(page: &Page, config: &AutoScroll, deadline: Instant) -> Result<ScrollReport, ScrapeError> {
+    if let Some(selector) = &config.until_selector {
+        extract::parse_selector(selector)?;
+    }
+    let started = Instant::now();
+    let stop_at = (started + Duration::from_millis(config.max_time_ms)).min(deadline);
+    let measure_script = measure_script(config.until_selector.as_deref());
+    let mut measure = self::measure(page, &measure_script).await?;
+    let mut scrolls = 0;
+    let stopped = loop {
+        if let Some(stop) = limit_reached(config, scrolls, measure.count, Instant::now() >= stop_at) {
+            break stop;
+        }
+        page.evaluate_expression("window.scrollBy(0, window.innerHeight)").await?;
+        scrolls += 1;
+        tokio::time::sleep(STEP_DELAY.min(stop_at.saturating_duration_since(Instant::now()))).await;
+        let before = measure.height;
+        measure = self::measure(page, &measure_script).await?;
+        if measure.bottom {
+            // at the end: give the feed `idle_ms` to append more
+            let idle_until = (Instant::now() + Duration::from_millis(config.idle_ms)).min(stop_at);
+            while measure.bottom && measure.height <= before && Instant::now() < idle_until {
+                tokio::time::sleep(POLL_INTERVAL).await;
+                measure = self::measure(page, &measure_script).await?;
+            }
+            if measure.bottom && measure.height <= before {
+                break ScrollStop::End;
+            }
+        }
+    };
+
+    let lazy_images = if config.lazy_images {
+        let limit = IMAGE_WAIT.min(deadline.saturating_duration_since(Instant::now()));
+        page.evaluate_expression(lazy_images_script(limit))
+            .await?
+            .into_value::<usize>()
+            .map_err(|e| ScrapeError::from(e.to_string()))?
+    } else {
+        page.evaluate_expression("window.scrollTo(0, 0)").await?;
+        0
+    };
+
+    Ok(ScrollReport {
+        scrolls,
+        height: measure.height,
+        matched: measure.count,
+        lazy_images,
+        stopped,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// Why scrolling should stop before the next screen, if it should
+fn limit_reached(config: &AutoScroll, scrolls: u32, matched: Option<usize>, out_of_time: bool) -> Option<ScrollStop> {
+    let target = config.until_count.unwrap_or(1);
+    if matched.is_some_and(|count| count >= target) {
+        Some(ScrollStop::Target)
+    } else if scrolls >= config.max_scrolls {
+        Some(ScrollStop::MaxScrolls)
+    } else if out_of_time {
+        Some(ScrollStop::MaxTime)
+    } else {
+        None
+    }
+}
+
+async fn measure(page: &Page, script: &str) -> Result<Measure, ScrapeError> {
+    page.evaluate_expression(script)
+        .await?
+        .into_value::<Measure>()
+        .map_err(|e| ScrapeError::from(e.to_string()))
+}
+
+fn measure_script(selector: Option<&str>) -> String {
+    let count = match selector {
+        Some(selector) => format!("document.querySelectorAll({}).length", serde_json::to_string(selector).unwrap_or_default()),
+        None => "null".to_string(),
+    };
+    format!(
+        "(() => {{ const el = document.scrollingElement || document.documentElement; \
+         return {{ height: el.scrollHeight, bottom: window.scrollY + window.innerHeight >= el.scrollHeight - 2, count: {} }}; }})()",
+        count,
+    )
+}
+
+/// Force lazy images to load, wait for them (up to `limit`) and scroll back to the top.
+/// Evaluates to the number of images that were still loading.
+fn lazy_images_script(limit: Duration) -> String {
+    format!(
+        "(async () => {{ \
+         for (const img of document.querySelectorAll('img')) {{ \
+           if (img.loading === 'lazy') img.loading = 'eager'; \
+           for (const [from, to] of [['data-src', 'src'], ['data-srcset', 'srcset']]) {{ \
+             const v = img.getAttribute(from); \
+             if (v && img.getAttribute(to) !== v) img.setAttribute(to, v); \
+           }} \
+         }} \
+         for (const s of document.querySelectorAll('source[data-srcset]')) s.srcset = s.dataset.srcset; \
+         const pending = [...document.images].filter((img) => !img.complete); \
+         await Promise.race([ \
+           Promise.all(pending.map((img) => new Promise((done) => {{ \
+             img.addEventListener('load', done, {{ once: true }}); \
+             img.addEventListener('error', done, {{ once: true }}); \
+           }}))), \
+           new Promise((done) => setTimeout(done, {})), \
+         ]); \
+         window.scrollTo(0, 0); \
+         return pending.length; }})()",
+        limit.as_millis(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stop_conditions() {
+        let config = AutoScroll { max_scrolls: 3, until_selector: Some("li".to_string()), until_count: Some(20), ..Default::default() };
+        assert_eq!(limit_reached(&config, 0, Some(5), false), None);
+        assert_eq!(limit_reached(&config, 1, Some(20), false), Some(ScrollStop::Target));
+        assert_eq!(limit_reached(&config, 3, Some(5), false), Some(ScrollStop::MaxScrolls));
+        assert_eq!(limit_reached(&config, 1, Some(5), true), Some(ScrollStop::MaxTime));
+
+        // a selector without a count stops at the first match
+        let config = AutoScroll { until_selector: Some("#footer".to_string()), ..Default::default() };
+        assert_eq!(limit_reached(&config, 2, Some(1), false), Some(ScrollStop::Target));
+        assert_eq!(limit_reached(&AutoScroll::default(), 2, None, false), None);
+    }
+
+    #[test]
+    fn selector_is_quoted() {
+        let script = measure_script(Some(r#"a[href="x"]"#));
+        assert!(script.contains(r#"document.querySelectorAll("a[href=\"x\"]").length"#));
+        assert!(measure_script(None).contains("count: null"));
+    }
+}
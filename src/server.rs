@@ -0,0 +1,662 @@
+use std::{borrow::Cow, sync::{Arc, Weak}, time::Duration};
+
+use kurosabi::{Kurosabi, context::ContextMiddleware};
+use urlencoding::decode;
+
+use super::{archive, browser::Engine, crawl, error::{ErrorCode, ScrapeError}, extract, jobs::JobStore, schema::{CaptureArchive, CaptureOptions, CookieFormat, CrawlRequest, ExtractRequest, JobRequest, PdfOptions, PdfRequest, RenderMode, RequestOptions, ScrapeSpec, ScraperResult, SessionInfo}, session};
+
+#[derive(Clone)]
+pub struct ScraperContext {
+    // store a Weak reference so the server's stored contexts do not keep the
+    // Engine alive forever; handlers should attempt to upgrade when needed.
+    pub engine: Weak<Engine>,
+    pub jobs: Arc<JobStore>,
+}
+
+impl ScraperContext {
+    /// Create a ScraperContext that holds a Weak reference to the engine.
+    pub fn from_engine(engine: &Arc<Engine>, jobs: Arc<JobStore>) -> Self {
+        ScraperContext { engine: Arc::downgrade(engine), jobs }
+    }
+}
+
+impl ContextMiddleware<ScraperContext> for ScraperContext {}
+
+/// JSON body (`ScraperResult::Failed`) and HTTP status for a failed request
+fn failure(err: ScrapeError) -> (serde_json::Value, u16) {
+    let status = err.http_status();
+    (serde_json::to_value(ScraperResult::from(err)).unwrap(), status)
+}
+
+/// Percent-decoded query value (the client encodes selectors, which may contain `&`, `#` or `;`)
+fn decoded(value: String) -> String {
+    decode(&value).map(|s| s.into_owned()).unwrap_or(value)
+}
+
+fn engine_unavailable() -> ScrapeError {
+    ScrapeError::new(ErrorCode::BrowserUnavailable, "Engine not available")
+}
+
+/// Render a PDF request (shared by GET and POST /pdf)
+async fn render_pdf(engine: &Weak<Engine>, req: &PdfRequest) -> Result<Vec<u8>, ScrapeError> {
+    let engine = engine.upgrade().ok_or_else(engine_unavailable)?;
+    engine.pdf(&req.url, Duration::from_millis(req.wait), &req.pdf, &req.options)
+        .await
+        .map_err(|e| e.context("Error rendering PDF"))
+}
+
+/// Register every endpoint on a server sharing `engine` and `jobs`.
+pub fn routes(engine: &Arc<Engine>, jobs: Arc<JobStore>) -> Kurosabi<ScraperContext> {
+    // The context holds only a Weak reference; this prevents the
+    // server from keeping the Engine alive by accident.
+    let ctx = ScraperContext::from_engine(engine, jobs);
+    let mut kurosabi = Kurosabi::with_context(ctx);
+
+    kurosabi.get("/", |mut c| async move {
+        c.res.text("Scraping server is running !!");
+        c
+    });
+
+    // Engine status endpoint
+    // ブラウザの稼働状態・再起動回数・タブ使用状況
+    kurosabi.get("/stats", |mut c| async move {
+        if let Some(engine) = c.c.engine.upgrade() {
+            c.res.json_value(&serde_json::to_value(engine.stats()).unwrap());
+        } else {
+            let (body, status) = failure(engine_unavailable());
+            c.res.json_value(&body);
+            c.res.set_status(status);
+        }
+        c
+    });
+
+    // Capture screenshot endpoint
+    // URL Query Parameters:
+    // - url: URL to capture
+    // - selector: (optional) CSS selector to capture only a specific element
+    // - timeout_ms / navigation_timeout_ms / selector_timeout_ms: (optional) per-request timeouts
+    // - wait_for: (optional) conditions to wait for, e.g. `load;network_idle:0:500;text:Hello` or a JSON array
+    // - actions: (optional) JSON array of Action run before capturing; a failing required step fails with `action_failed`
+    // - auto_scroll: (optional) `true` or a JSON AutoScroll; expands infinite scroll and lazy images before capturing
+    // - format: (optional) `png` (default) | `jpeg` | `webp`; quality: (optional) 0-100 for jpeg / webp (default 80)
+    // - max_width / max_height: (optional) scale the image down to fit (pixels)
+    // - thumbnail: (optional) N, the first screen scaled down to fit N x N (jpeg unless format is given)
+    // - clip: (optional) `x,y,width,height` in CSS pixels, full-page captures only
+    // - padding: (optional) CSS pixels added around each captured element
+    // Response: the image, with a matching Content-Type
+    //
+    // Multi-element capture (when `all=true` or `selectors` is given):
+    // - selectors: (optional) `;`-separated CSS selectors, in addition to `selector`; every match of each is captured
+    // - all: (optional) `true` to capture every match of `selector` instead of the first
+    // - max_elements: (optional) at most this many images (default 100); the manifest is marked `truncated` beyond it
+    // - archive: (optional) `zip` (default) | `multipart`
+    // Response: application/zip with `manifest.json` and `element-N.<ext>`, or multipart/mixed with the manifest as the first part
+    //
+    kurosabi.get("/capture", |mut c| async move {
+        let url = c.req.path.get_query("url");
+        let parsed = RequestOptions::from_query(|key| c.req.path.get_query(key))
+            .and_then(|options| Ok((options, CaptureOptions::from_query(|key| c.req.path.get_query(key))?)));
+        let (options, image) = match parsed {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                let (body, status) = failure(e);
+                c.res.json_value(&body);
+                c.res.set_status(status);
+                return c;
+            }
+        };
+        let wait_duration = c.req.path.get_query("wait")
+            .and_then(|s| s.parse::<u64>().ok())
+            .map(std::time::Duration::from_millis)
+            .unwrap_or(std::time::Duration::from_millis(0));
+        if let Some(url) = url {
+            let url = decode(&url).unwrap_or_else(|_| Cow::Borrowed(url.as_str())).to_string();
+            let selector = c.req.path.get_query("selector").map(decoded);
+            let listed = c.req.path.get_query("selectors").map(decoded);
+            let all = c.req.path.get_query("all").is_some_and(|s| s == "true");
+            if all || listed.is_some() {
+                let selectors = selector.iter().chain(listed.iter())
+                    .flat_map(|s| s.split(';'))
+                    .filter(|s| !s.trim().is_empty())
+                    .map(|s| s.to_string())
+                    .collect::<Vec<String>>();
+                let archive = match c.req.path.get_query("archive") {
+                    Some(s) => CaptureArchive::from_query(&s)
+                        .ok_or_else(|| ScrapeError::invalid_request(format!("Invalid archive '{}' (expected zip or multipart)", s))),
+                    None => Ok(CaptureArchive::default()),
+                };
+                let archive = match archive {
+                    Ok(archive) => archive,
+                    Err(e) => {
+                        let (body, status) = failure(e);
+                        c.res.json_value(&body);
+                        c.res.set_status(status);
+                        return c;
+                    }
+                };
+                if let Some(engine) = c.c.engine.upgrade() {
+                    match engine.capture_elements(&url, &selectors, wait_duration, &image, &options).await {
+                        Ok(captures) => {
+                            let (data, content_type) = archive::pack(&captures, archive);
+                            c.res.binary(&data);
+                            c.res.header.set("Content-type", &content_type);
+                        }
+                        Err(e) => {
+                            let (body, status) = failure(e.context("Error capturing elements"));
+                            c.res.json_value(&body);
+                            c.res.set_status(status);
+                        }
+                    }
+                } else {
+                    let (body, status) = failure(engine_unavailable());
+                    c.res.json_value(&body);
+                    c.res.set_status(status);
+                }
+            } else if let Some(selector) = selector {
+                // attempt to upgrade Weak -> Arc
+                if let Some(engine) = c.c.engine.upgrade() {
+                    let image_data = engine.capture_element(&url, &selector, wait_duration, &image, &options).await;
+                    match image_data {
+                        Ok(data) => {
+                            c.res.binary(&data);
+                            c.res.header.set("Content-type", image.format().content_type());
+                        }
+                        Err(e) => {
+                            let (body, status) = failure(e.context("Error capturing screenshot"));
+                            c.res.json_value(&body);
+                            c.res.set_status(status);
+                        }
+                    }
+                } else {
+                    let (body, status) = failure(engine_unavailable());
+                    c.res.json_value(&body);
+                    c.res.set_status(status);
+                }
+            } else {
+                if let Some(engine) = c.c.engine.upgrade() {
+                    let image_data = engine.capture_full_page(&url, wait_duration, &image, &options).await;
+                    match image_data {
+                        Ok(data) => {
+                            c.res.binary(&data);
+                            c.res.header.set("Content-type", image.format().content_type());
+                        }
+                        Err(e) => {
+                            let (body, status) = failure(e.context("Error capturing screenshot"));
+                            c.res.json_value(&body);
+                            c.res.set_status(status);
+                        }
+                    }
+                } else {
+                    let (body, status) = failure(engine_unavailable());
+                    c.res.json_value(&body);
+                    c.res.set_status(status);
+                }
+            }
+        } else {
+            let (body, status) = failure(ScrapeError::invalid_request("Missing 'url' query parameter"));
+            c.res.json_value(&body);
+            c.res.set_status(status);
+        }
+        c
+    });
+
+    // PDF endpoint
+    // ページを Page.printToPDF で PDF にする (headless Chromium のみ)
+    // URL Query Parameters (GET) / PdfRequest JSON (POST):
+    // - url: URL to print
+    // - wait: (optional) milliseconds to wait before printing
+    // - paper: (optional) `a3` | `a4` (default) | `a5` | `letter` | `legal` | `tabloid`,
+    //   or paper_width_mm / paper_height_mm
+    // - margin_mm / margin_top_mm / margin_right_mm / margin_bottom_mm / margin_left_mm: (optional) margins, default 10mm
+    // - landscape / print_background / prefer_css_page_size: (optional) `true` to enable
+    // - scale: (optional) 0.1 - 2.0
+    // - header_template / footer_template: (optional) HTML templates (classes `pageNumber`, `totalPages`, `title`, `url`, `date`)
+    // - page_ranges: (optional) e.g. `1-3,5`
+    // - plus the request options of /capture (timeouts, wait_for, block, emulation, proxy, session, actions, auto_scroll)
+    // Response: application/pdf
+    //
+    kurosabi.get("/pdf", |mut c| async move {
+        let Some(url) = c.req.path.get_query("url") else {
+            let (body, status) = failure(ScrapeError::invalid_request("Missing 'url' query parameter"));
+            c.res.json_value(&body);
+            c.res.set_status(status);
+            return c;
+        };
+        let parsed = RequestOptions::from_query(|key| c.req.path.get_query(key))
+            .and_then(|options| Ok((options, PdfOptions::from_query(|key| c.req.path.get_query(key))?)));
+        let (options, pdf) = match parsed {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                let (body, status) = failure(e);
+                c.res.json_value(&body);
+                c.res.set_status(status);
+                return c;
+            }
+        };
+        let req = PdfRequest {
+            url: decode(&url).unwrap_or_else(|_| Cow::Borrowed(url.as_str())).to_string(),
+            wait: c.req.path.get_query("wait").and_then(|s| s.parse::<u64>().ok()).unwrap_or(0),
+            pdf,
+            options,
+        };
+        match render_pdf(&c.c.engine, &req).await {
+            Ok(data) => {
+                c.res.binary(&data);
+                c.res.header.set("Content-type", "application/pdf");
+            }
+            Err(e) => {
+                let (body, status) = failure(e);
+                c.res.json_value(&body);
+                c.res.set_status(status);
+            }
+        }
+        c
+    });
+
+    kurosabi.post("/pdf", |mut c| async move {
+        let body = c.req.body_string().await.unwrap_or_default();
+        let req = match serde_json::from_str::<PdfRequest>(&body) {
+            Ok(req) => req,
+            Err(e) => {
+                let (body, status) = failure(ScrapeError::invalid_request(format!("Invalid request: {}", e)));
+                c.res.json_value(&body);
+                c.res.set_status(status);
+                return c;
+            }
+        };
+        match render_pdf(&c.c.engine, &req).await {
+            Ok(data) => {
+                c.res.binary(&data);
+                c.res.header.set("Content-type", "application/pdf");
+            }
+            Err(e) => {
+                let (body, status) = failure(e);
+                c.res.json_value(&body);
+                c.res.set_status(status);
+            }
+        }
+        c
+    });
+
+    // Scraping endpoint
+    // スクレイピング用のエンドポイント
+    // Url Query Parameters:
+    // - url: URL to scrape
+    // - selectors: Semicolon-separated list of CSS selectors to extract contents. `;` is used as separator
+    // - text_selector: (optional) CSS selector to extract text content
+    // - waiting_selector: (optional) CSS selector to wait for before scraping
+    // - render: (optional) `true` (default, headless Chromium), `false` (static HTML only) or
+    //   `auto` (static HTML, falling back to Chromium when waiting_selector is not in it)
+    // - ignore_robots: (optional) `true` to skip the robots.txt check
+    // - timeout_ms / navigation_timeout_ms / selector_timeout_ms: (optional) per-request timeouts,
+    //   capped by the server maxima
+    // - partial_results: (optional) `true` to return what was loaded when a timeout hits
+    // - wait_for: (optional) conditions to wait for before extracting, `;`-separated short form
+    //   (`dom_content_loaded`, `load`, `network_idle[:N[:ms]]`, `selector:CSS`, `text:TEXT`, `js:EXPR`)
+    //   or a JSON array of WaitCondition
+    // - actions: (optional) JSON array of Action (click, type, press, hover, select, scroll, wait, eval)
+    //   run after wait_for and before waiting_selector; per-step results are returned in `results.actions`
+    // - auto_scroll: (optional) `true` or a JSON AutoScroll; scrolls until the page stops growing
+    //   (or a cap / target count is reached) after the actions, reported in `results.scroll`
+    //
+    // Example:
+    // /scraping?url=https://example.com
+    // /scraping?url=https://ja.wikipedia.org/wiki/%E5%9C%8F%E8%AB%96&text_selector=.mw-body-content
+    // 
+    kurosabi.get("/scraping", |mut c| async move {
+        let url = c.req.path.get_query("url");
+        let selectors_owner = c.req.path.get_query("selectors")
+            .map(decoded)
+            .map(|s| s.split(';').map(|item| item.to_string()).collect::<Vec<String>>())
+            .unwrap_or_else(|| vec![]);
+        let selectors = selectors_owner.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
+        let text_selector = c.req.path.get_query("text_selector").map(decoded);
+        let waiting_selector = c.req.path.get_query("waiting_selector").map(decoded);
+        let render = c.req.path.get_query("render")
+            .and_then(|s| RenderMode::from_query(&s))
+            .unwrap_or_default();
+        let options = match RequestOptions::from_query(|key| c.req.path.get_query(key)) {
+            Ok(options) => options,
+            Err(e) => {
+                let (body, status) = failure(e);
+                c.res.json_value(&body);
+                c.res.set_status(status);
+                return c;
+            }
+        };
+        if let Some(url) = url {
+            let url = decode(&url).unwrap_or_else(|_| Cow::Borrowed(url.as_str())).to_string();
+            if let Some(engine) = c.c.engine.upgrade() {
+                let result = engine.scraping(&url, selectors, text_selector.as_deref(), waiting_selector.as_deref(), render, &options).await;
+                match result {
+                    Ok(scrape_results) => {
+                        let result = ScraperResult::Success {
+                            status: scrape_results.status.unwrap_or(200),
+                            url: url.clone(),
+                            results: scrape_results,
+                        };
+                        c.res.json_value(&serde_json::to_value(result).unwrap());
+                    }
+                    Err(e) => {
+                        let (body, status) = failure(e.context("Error during scraping"));
+                        c.res.json_value(&body);
+                        c.res.set_status(status);
+                    }
+                }
+            } else {
+                let (body, status) = failure(engine_unavailable());
+                c.res.json_value(&body);
+                c.res.set_status(status);
+            }
+        } else {
+            let (body, status) = failure(ScrapeError::invalid_request("Missing 'url' query parameter"));
+            c.res.json_value(&body);
+            c.res.set_status(status);
+        }
+        c
+    });
+
+    // Declarative scraping endpoint
+    // 宣言的な抽出仕様 (spec.sample.json 形式) を受け取るエンドポイント
+    // Body: ScrapeSpec JSON
+    // Response: { "status": 200, "url": "...", "results": { "<name>": [...] } } (out.json 形式)
+    //
+    kurosabi.post("/scrape", |mut c| async move {
+        let body = c.req.body_string().await.unwrap_or_default();
+        let spec = match serde_json::from_str::<ScrapeSpec>(&body) {
+            Ok(spec) => spec,
+            Err(e) => {
+                let (body, status) = failure(ScrapeError::invalid_request(format!("Invalid spec: {}", e)));
+                c.res.json_value(&body);
+                c.res.set_status(status);
+                return c;
+            }
+        };
+        if let Some(engine) = c.c.engine.upgrade() {
+            match engine.scrape_spec(&spec).await {
+                Ok(spec_results) => {
+                    c.res.json_value(&serde_json::to_value(spec_results).unwrap());
+                }
+                Err(e) => {
+                    let (body, status) = failure(e.context("Error during scraping"));
+                    c.res.json_value(&body);
+                    c.res.set_status(status);
+                }
+            }
+        } else {
+            let (body, status) = failure(engine_unavailable());
+            c.res.json_value(&body);
+            c.res.set_status(status);
+        }
+        c
+    });
+
+    // Offline extraction endpoint
+    // 保存済みHTMLなど、呼び出し側が渡したドキュメントから抽出する (ブラウザ不使用)
+    // Body: { "url": "...", "document": "<html>...", "selectors": [...], "text_selector": "..." }
+    // Response: /scraping と同じ形式
+    //
+    kurosabi.post("/extract", |mut c| async move {
+        let body = c.req.body_string().await.unwrap_or_default();
+        let req = match serde_json::from_str::<ExtractRequest>(&body) {
+            Ok(req) => req,
+            Err(e) => {
+                let (body, status) = failure(ScrapeError::invalid_request(format!("Invalid request: {}", e)));
+                c.res.json_value(&body);
+                c.res.set_status(status);
+                return c;
+            }
+        };
+        let selectors = req.selectors.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
+        match extract::extract(&req.document, &req.url, &selectors, req.text_selector.as_deref()) {
+            Ok(scrape_results) => {
+                let result = ScraperResult::Success {
+                    status: 200,
+                    url: req.url.clone(),
+                    results: scrape_results,
+                };
+                c.res.json_value(&serde_json::to_value(result).unwrap());
+            }
+            Err(e) => {
+                let (body, status) = failure(e.context("Error during extraction"));
+                c.res.json_value(&body);
+                c.res.set_status(status);
+            }
+        }
+        c
+    });
+
+    // Crawl endpoint
+    // seeds から範囲内のリンクを辿り、各ページをスクレイピングする
+    // Body: CrawlRequest JSON
+    // {
+    //   "seeds": ["https://example.com/"],
+    //   "scope": { "domain": "same_host" | "same_domain" | "any", "path_prefix": "/docs/", "include": [regex], "exclude": [regex] },
+    //   "max_depth": 1, "max_pages": 50, "concurrency": 4,
+    //   "selectors": [...], "text_selector": "...", "waiting_selector": "...", "render": "true" | "false" | "auto"
+    // }
+    // Response: { "pages": [ { "url": "...", "depth": 0, "result": <ScraperResult> } ] }
+    //
+    kurosabi.post("/crawl", |mut c| async move {
+        let body = c.req.body_string().await.unwrap_or_default();
+        let req = match serde_json::from_str::<CrawlRequest>(&body) {
+            Ok(req) => req,
+            Err(e) => {
+                let (body, status) = failure(ScrapeError::invalid_request(format!("Invalid request: {}", e)));
+                c.res.json_value(&body);
+                c.res.set_status(status);
+                return c;
+            }
+        };
+        if let Err(e) = req.validate() {
+            let (body, status) = failure(e);
+            c.res.json_value(&body);
+            c.res.set_status(status);
+            return c;
+        }
+        if let Some(engine) = c.c.engine.upgrade() {
+            match crawl::crawl(&engine, &req).await {
+                Ok(crawl_results) => {
+                    c.res.json_value(&serde_json::to_value(crawl_results).unwrap());
+                }
+                Err(e) => {
+                    let (body, status) = failure(e.context("Error during crawl"));
+                    c.res.json_value(&body);
+                    c.res.set_status(status);
+                }
+            }
+        } else {
+            let (body, status) = failure(engine_unavailable());
+            c.res.json_value(&body);
+            c.res.set_status(status);
+        }
+        c
+    });
+
+    // Async job endpoints
+    // 長時間かかる処理をジョブとして受け付け、IDでポーリングする
+    // POST /jobs        Body: { "kind": "scrape" | "spec" | "crawl", ... } -> JobInfo
+    //                   (scrape: url/selectors/text_selector/waiting_selector/render,
+    //                    spec: ScrapeSpec, crawl: CrawlRequest)
+    // GET /jobs/:id     -> JobInfo (status, pages_done, result / error)
+    // DELETE /jobs/:id  -> JobInfo (cancel; pages used by the job are closed)
+    //
+    kurosabi.post("/jobs", |mut c| async move {
+        let body = c.req.body_string().await.unwrap_or_default();
+        let req = match serde_json::from_str::<JobRequest>(&body) {
+            Ok(req) => req,
+            Err(e) => {
+                let (body, status) = failure(ScrapeError::invalid_request(format!("Invalid job: {}", e)));
+                c.res.json_value(&body);
+                c.res.set_status(status);
+                return c;
+            }
+        };
+        if let Err(e) = req.validate() {
+            let (body, status) = failure(e);
+            c.res.json_value(&body);
+            c.res.set_status(status);
+            return c;
+        }
+        if let Some(engine) = c.c.engine.upgrade() {
+            match c.c.jobs.submit(engine, req) {
+                Ok(info) => {
+                    c.res.json_value(&serde_json::to_value(info).unwrap());
+                    c.res.set_status(202);
+                }
+                Err(e) => {
+                    let (body, status) = failure(e);
+                    c.res.json_value(&body);
+                    c.res.set_status(status);
+                }
+            }
+        } else {
+            let (body, status) = failure(engine_unavailable());
+            c.res.json_value(&body);
+            c.res.set_status(status);
+        }
+        c
+    });
+
+    kurosabi.get("/jobs/:id", |mut c| async move {
+        let id = c.req.path.get_field("id").unwrap_or_default();
+        match c.c.jobs.get(&id) {
+            Some(info) => {
+                c.res.json_value(&serde_json::to_value(info).unwrap());
+            }
+            None => {
+                let (body, status) = failure(ScrapeError::new(ErrorCode::NotFound, format!("Job not found: {}", id)));
+                c.res.json_value(&body);
+                c.res.set_status(status);
+            }
+        }
+        c
+    });
+
+    kurosabi.delete("/jobs/:id", |mut c| async move {
+        let id = c.req.path.get_field("id").unwrap_or_default();
+        match c.c.jobs.cancel(&id) {
+            Some(info) => {
+                c.res.json_value(&serde_json::to_value(info).unwrap());
+            }
+            None => {
+                let (body, status) = failure(ScrapeError::new(ErrorCode::NotFound, format!("Job not found: {}", id)));
+                c.res.json_value(&body);
+                c.res.set_status(status);
+            }
+        }
+        c
+    });
+
+    // Named session endpoints
+    // リクエストの `session=<id>` で Cookie・localStorage・sessionStorage を引き継ぐ
+    // GET /sessions                -> [SessionInfo]
+    // GET /sessions/:id            -> Session (cookies, local_storage, session_storage)
+    // DELETE /sessions/:id         -> SessionInfo of the deleted session
+    // GET /sessions/:id/cookies    ?format=json|netscape -> cookies (JSON array or cookies.txt)
+    // POST /sessions/:id/cookies   ?format=json|netscape Body: cookies -> SessionInfo (merged, session created if needed)
+    //
+    kurosabi.get("/sessions", |mut c| async move {
+        if let Some(engine) = c.c.engine.upgrade() {
+            c.res.json_value(&serde_json::to_value(engine.sessions.list()).unwrap());
+        } else {
+            let (body, status) = failure(engine_unavailable());
+            c.res.json_value(&body);
+            c.res.set_status(status);
+        }
+        c
+    });
+
+    kurosabi.get("/sessions/:id", |mut c| async move {
+        let id = c.req.path.get_field("id").unwrap_or_default();
+        let session = match c.c.engine.upgrade() {
+            Some(engine) => engine.sessions.get(&id).ok_or_else(|| ScrapeError::new(ErrorCode::NotFound, format!("Session not found: {}", id))),
+            None => Err(engine_unavailable()),
+        };
+        match session {
+            Ok(session) => {
+                c.res.json_value(&serde_json::to_value(session).unwrap());
+            }
+            Err(e) => {
+                let (body, status) = failure(e);
+                c.res.json_value(&body);
+                c.res.set_status(status);
+            }
+        }
+        c
+    });
+
+    kurosabi.delete("/sessions/:id", |mut c| async move {
+        let id = c.req.path.get_field("id").unwrap_or_default();
+        let session = match c.c.engine.upgrade() {
+            Some(engine) => engine.sessions.delete(&id).ok_or_else(|| ScrapeError::new(ErrorCode::NotFound, format!("Session not found: {}", id))),
+            None => Err(engine_unavailable()),
+        };
+        match session {
+            Ok(session) => {
+                c.res.json_value(&serde_json::to_value(SessionInfo::from(&session)).unwrap());
+            }
+            Err(e) => {
+                let (body, status) = failure(e);
+                c.res.json_value(&body);
+                c.res.set_status(status);
+            }
+        }
+        c
+    });
+
+    kurosabi.get("/sessions/:id/cookies", |mut c| async move {
+        let id = c.req.path.get_field("id").unwrap_or_default();
+        let exported = CookieFormat::from_query(c.req.path.get_query("format").as_deref()).and_then(|format| {
+            let engine = c.c.engine.upgrade().ok_or_else(engine_unavailable)?;
+            let session = engine.sessions.get(&id).ok_or_else(|| ScrapeError::new(ErrorCode::NotFound, format!("Session not found: {}", id)))?;
+            Ok((format, session::export_cookies(&session.cookies, format)))
+        });
+        match exported {
+            Ok((CookieFormat::Json, text)) => {
+                c.res.text(&text);
+                c.res.header.set("Content-type", "application/json");
+            }
+            Ok((CookieFormat::Netscape, text)) => {
+                c.res.text(&text);
+                c.res.header.set("Content-type", "text/plain; charset=utf-8");
+            }
+            Err(e) => {
+                let (body, status) = failure(e);
+                c.res.json_value(&body);
+                c.res.set_status(status);
+            }
+        }
+        c
+    });
+
+    kurosabi.post("/sessions/:id/cookies", |mut c| async move {
+        let id = c.req.path.get_field("id").unwrap_or_default();
+        let format = c.req.path.get_query("format");
+        let body = c.req.body_string().await.unwrap_or_default();
+        let imported = CookieFormat::from_query(format.as_deref())
+            .and_then(|format| session::import_cookies(&body, format))
+            .and_then(|cookies| {
+                let engine = c.c.engine.upgrade().ok_or_else(engine_unavailable)?;
+                engine.sessions.import_cookies(&id, cookies)
+            });
+        match imported {
+            Ok(session) => {
+                c.res.json_value(&serde_json::to_value(SessionInfo::from(&session)).unwrap());
+            }
+            Err(e) => {
+                let (body, status) = failure(e);
+                c.res.json_value(&body);
+                c.res.set_status(status);
+            }
+        }
+        c
+    });
+
+    kurosabi.not_found_handler(|mut c| async move {
+        c.res.text("invalid endpoint");
+        c
+    });
+
+    kurosabi
+}
@@ -0,0 +1,266 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+
+use super::error::{ErrorCode, ScrapeError};
+use super::schema::{CookieFormat, Session, SessionCookie, SessionInfo};
+
+/// Sessions kept in memory at most
+const MAX_SESSIONS: usize = 1000;
+
+/// Longest accepted session ID
+const MAX_ID_LEN: usize = 64;
+
+/// Storage of one origin read back from a page
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct OriginStorage {
+    pub origin: String,
+    pub local: BTreeMap<String, String>,
+    pub session: BTreeMap<String, String>,
+}
+
+/// Named sessions: cookies and web storage carried from one request to the next.
+///
+/// Requests without a session never touch the store. A request naming an unknown
+/// session starts from an empty one, which is created when the request saves it.
+#[derive(Default)]
+pub struct SessionStore {
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+impl SessionStore {
+    pub fn new() -> Self {
+        SessionStore::default()
+    }
+
+    /// `[A-Za-z0-9_.-]`, 1 to 64 characters
+    pub fn validate_id(id: &str) -> Result<(), ScrapeError> {
+        let valid = !id.is_empty()
+            && id.len() <= MAX_ID_LEN
+            && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+        if valid {
+            Ok(())
+        } else {
+            Err(ScrapeError::invalid_request(format!(
+                "Invalid session id '{}': use 1-{} letters, digits, '_', '-' or '.'",
+                id, MAX_ID_LEN,
+            )))
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<Session> {
+        self.sessions.lock().unwrap().get(id).cloned()
+    }
+
+    /// State to restore for a request: the stored session, or an empty one.
+    pub fn checkout(&self, id: &str) -> Result<Session, ScrapeError> {
+        Self::validate_id(id)?;
+        Ok(self.get(id).unwrap_or_else(|| Session { id: id.to_string(), ..Default::default() }))
+    }
+
+    /// Store the state a request ended with. The cookie jar is replaced as a whole
+    /// (so cookies the site deleted stay deleted); storage is replaced per origin.
+    pub fn save(&self, id: &str, cookies: Vec<SessionCookie>, storage: Option<OriginStorage>) -> Result<Session, ScrapeError> {
+        self.update(id, |session| {
+            session.cookies = cookies;
+            if let Some(storage) = storage {
+                set_origin(&mut session.local_storage, &storage.origin, storage.local);
+                set_origin(&mut session.session_storage, &storage.origin, storage.session);
+            }
+        })
+    }
+
+    /// Add cookies to a session (creating it), replacing cookies with the same name, domain and path.
+    pub fn import_cookies(&self, id: &str, cookies: Vec<SessionCookie>) -> Result<Session, ScrapeError> {
+        self.update(id, |session| {
+            for cookie in cookies {
+                session.cookies.retain(|c| !(c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path));
+                session.cookies.push(cookie);
+            }
+        })
+    }
+
+    pub fn delete(&self, id: &str) -> Option<Session> {
+        self.sessions.lock().unwrap().remove(id)
+    }
+
+    pub fn list(&self) -> Vec<SessionInfo> {
+        let mut sessions: Vec<SessionInfo> = self.sessions.lock().unwrap().values().map(SessionInfo::from).collect();
+        sessions.sort_by(|a, b| a.id.cmp(&b.id));
+        sessions
+    }
+
+    fn update(&self, id: &str, apply: impl FnOnce(&mut Session)) -> Result<Session, ScrapeError> {
+        Self::validate_id(id)?;
+        let mut sessions = self.sessions.lock().unwrap();
+        if !sessions.contains_key(id) && sessions.len() >= MAX_SESSIONS {
+            return Err(ScrapeError::new(ErrorCode::Busy, "session store is full"));
+        }
+        let session = sessions.entry(id.to_string()).or_insert_with(|| Session { id: id.to_string(), ..Default::default() });
+        apply(session);
+        session.updated_at = unix_millis();
+        Ok(session.clone())
+    }
+}
+
+fn set_origin(areas: &mut BTreeMap<String, BTreeMap<String, String>>, origin: &str, items: BTreeMap<String, String>) {
+    if items.is_empty() {
+        areas.remove(origin);
+    } else {
+        areas.insert(origin.to_string(), items);
+    }
+}
+
+/// Serialize cookies for export
+pub fn export_cookies(cookies: &[SessionCookie], format: CookieFormat) -> String {
+    match format {
+        CookieFormat::Json => serde_json::to_string_pretty(cookies).unwrap_or_default(),
+        CookieFormat::Netscape => to_netscape(cookies),
+    }
+}
+
+/// Parse cookies for import; malformed input is an `invalid_request`.
+pub fn import_cookies(data: &str, format: CookieFormat) -> Result<Vec<SessionCookie>, ScrapeError> {
+    match format {
+        CookieFormat::Json => serde_json::from_str(data).map_err(|e| ScrapeError::invalid_request(format!("Invalid cookie JSON: {}", e))),
+        CookieFormat::Netscape => from_netscape(data),
+    }
+}
+
+/// Netscape cookies.txt: `domain  subdomains  path  secure  expires  name  value`, tab-separated;
+/// HttpOnly cookies are prefixed with `#HttpOnly_`, session cookies expire at 0.
+fn to_netscape(cookies: &[SessionCookie]) -> String {
+    let mut out = String::from("# Netscape HTTP Cookie File\n");
+    for cookie in cookies {
+        let flag = |b: bool| if b { "TRUE" } else { "FALSE" };
+        out.push_str(&format!(
+            "{}{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            if cookie.http_only { "#HttpOnly_" } else { "" },
+            cookie.domain,
+            flag(cookie.domain.starts_with('.')),
+            cookie.path,
+            flag(cookie.secure),
+            cookie.expires.map(|e| e.max(0.0) as u64).unwrap_or(0),
+            cookie.name,
+            cookie.value,
+        ));
+    }
+    out
+}
+
+fn from_netscape(data: &str) -> Result<Vec<SessionCookie>, ScrapeError> {
+    let mut cookies = Vec::new();
+    for (number, line) in data.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        let (line, http_only) = match line.strip_prefix("#HttpOnly_") {
+            Some(rest) => (rest, true),
+            None => (line, false),
+        };
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        let invalid = |reason: &str| ScrapeError::invalid_request(format!("Invalid cookies.txt line {}: {}", number + 1, reason));
+        if fields.len() < 6 {
+            return Err(invalid("expected 7 tab-separated fields"));
+        }
+        let subdomains = fields[1].eq_ignore_ascii_case("TRUE");
+        let domain = match (subdomains, fields[0].starts_with('.')) {
+            (true, false) => format!(".{}", fields[0]),
+            _ => fields[0].to_string(),
+        };
+        let expires: u64 = fields[4].parse().map_err(|_| invalid("expires is not a number"))?;
+        cookies.push(SessionCookie {
+            name: fields[5].to_string(),
+            value: fields.get(6).copied().unwrap_or("").to_string(),
+            domain,
+            path: fields[2].to_string(),
+            expires: (expires > 0).then_some(expires as f64),
+            http_only,
+            secure: fields[3].eq_ignore_ascii_case("TRUE"),
+            same_site: None,
+        });
+    }
+    Ok(cookies)
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cookie(name: &str, domain: &str) -> SessionCookie {
+        SessionCookie {
+            name: name.to_string(),
+            value: "v".to_string(),
+            domain: domain.to_string(),
+            path: "/".to_string(),
+            expires: None,
+            http_only: false,
+            secure: false,
+            same_site: None,
+        }
+    }
+
+    #[test]
+    fn netscape_round_trip() {
+        let cookies = vec![
+            SessionCookie { expires: Some(1_900_000_000.0), secure: true, ..cookie("consent", ".example.com") },
+            SessionCookie { http_only: true, value: String::new(), ..cookie("sid", "login.example.com") },
+        ];
+        let text = export_cookies(&cookies, CookieFormat::Netscape);
+        assert!(text.contains(".example.com\tTRUE\t/\tTRUE\t1900000000\tconsent\tv\n"));
+        assert!(text.contains("#HttpOnly_login.example.com\tFALSE\t/\tFALSE\t0\tsid\t\n"));
+        assert_eq!(import_cookies(&text, CookieFormat::Netscape).unwrap(), cookies);
+
+        // curl writes host names without the leading dot
+        let curl = "example.org\tTRUE\t/\tFALSE\t0\ta\tb\r\n";
+        assert_eq!(import_cookies(curl, CookieFormat::Netscape).unwrap()[0].domain, ".example.org");
+        assert!(import_cookies("example.org\tTRUE\t/", CookieFormat::Netscape).is_err());
+    }
+
+    #[test]
+    fn json_import_aliases() {
+        // field names used by browser cookie export extensions
+        let json = r#"[{"name": "a", "value": "1", "domain": ".example.com", "httpOnly": true, "expirationDate": 1900000000.5}]"#;
+        let cookies = import_cookies(json, CookieFormat::Json).unwrap();
+        assert!(cookies[0].http_only);
+        assert_eq!((cookies[0].path.as_str(), cookies[0].expires), ("/", Some(1_900_000_000.5)));
+    }
+
+    #[test]
+    fn store_sessions() {
+        let store = SessionStore::new();
+        assert!(store.checkout("../etc").is_err());
+        assert!(store.checkout("login").unwrap().cookies.is_empty());
+        assert!(store.get("login").is_none());
+
+        store.import_cookies("login", vec![cookie("a", ".example.com"), cookie("b", ".example.com")]).unwrap();
+        store.import_cookies("login", vec![SessionCookie { value: "new".to_string(), ..cookie("a", ".example.com") }]).unwrap();
+        let session = store.checkout("login").unwrap();
+        assert_eq!(session.cookies.len(), 2);
+        assert!(session.cookies.iter().any(|c| c.name == "a" && c.value == "new"));
+
+        let storage = OriginStorage {
+            origin: "https://example.com".to_string(),
+            local: BTreeMap::from([("token".to_string(), "t".to_string())]),
+            session: BTreeMap::new(),
+        };
+        let saved = store.save("login", vec![cookie("a", ".example.com")], Some(storage)).unwrap();
+        assert_eq!(saved.cookies.len(), 1);
+        assert_eq!(saved.local_storage["https://example.com"]["token"], "t");
+        assert!(saved.session_storage.is_empty());
+        assert_eq!(store.list()[0].origins, vec!["https://example.com".to_string()]);
+
+        assert!(store.delete("login").is_some());
+        assert!(store.list().is_empty());
+    }
+}
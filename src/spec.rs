@@ -0,0 +1,135 @@
+use std::collections::BTreeMap;
+
+use scraper::{Html, Selector};
+
+use super::error::{ErrorCode, ScrapeError};
+use super::extract::document_base;
+use super::schema::{OutputSpec, ScrapeSpec};
+
+use super::utils;
+
+/// Evaluate a `ScrapeSpec` against a rendered document.
+///
+/// `url` is the final page URL and is used as the base for `absolute` attributes.
+/// Every name in the spec is present in the result, even when nothing matched.
+pub fn evaluate(spec: &ScrapeSpec, document: &str, url: &str) -> Result<BTreeMap<String, Vec<String>>, ScrapeError> {
+    let fragments = Html::parse_document(document);
+    let base_url = document_base(&fragments, url);
+
+    let mut results: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for entry in &spec.selectors {
+        let values = results.entry(entry.name.clone()).or_default();
+        // fallback: a `first` entry is only used while the name is still empty
+        if entry.first && !values.is_empty() {
+            continue;
+        }
+
+        let sel = Selector::parse(&entry.selector)
+            .map_err(|e| ScrapeError::new(
+                ErrorCode::InvalidSelector,
+                format!("invalid selector '{}' for '{}': {}", entry.selector, entry.name, e),
+            ))?;
+
+        let matched = fragments.select(&sel).filter_map(|elem| {
+            let value = match &entry.output {
+                OutputSpec::Text => {
+                    let text = elem.text().collect::<String>();
+                    if spec.normalize { normalize_whitespace(&text) } else { text.trim().to_string() }
+                }
+                OutputSpec::Html => elem.inner_html(),
+                OutputSpec::Attr { attr, absolute } => {
+                    let value = elem.value().attr(attr)?.trim();
+                    // links that do not resolve to http(s) (mailto:, javascript:) are dropped
+                    if *absolute { utils::resolve_link(&base_url, value)? } else { value.to_string() }
+                }
+            };
+            if value.is_empty() { None } else { Some(value) }
+        });
+
+        for value in matched {
+            if entry.unique && values.contains(&value) {
+                continue;
+            }
+            values.push(value);
+            if entry.first {
+                break;
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+/// Collapse runs of whitespace into a single space and trim both ends.
+fn normalize_whitespace(input: &str) -> String {
+    input.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOC: &str = r#"<html lang="ja"><head>
+        <title>  Example   Title </title>
+        <meta property="og:title" content="OG Title">
+        <link rel="icon" href="/favicon.ico">
+        </head><body>
+        <h2>A</h2><h2>B</h2><h2>A</h2><h3>C</h3>
+        <a href="/x">x</a><a href="https://other.example/y">y</a><a href="/x">x2</a>
+        </body></html>"#;
+
+    fn spec(json: &str) -> ScrapeSpec {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn fallback_first() {
+        let s = spec(r#"{ "url": "", "selectors": [
+            { "name": "title", "selector": "title", "output": { "type": "text" }, "first": true },
+            { "name": "title", "selector": "meta[property='og:title']", "output": { "type": "attr", "attr": "content" }, "first": true },
+            { "name": "site_name", "selector": "meta[property='og:site_name']", "output": { "type": "attr", "attr": "content" }, "first": true }
+        ]}"#);
+        let res = evaluate(&s, DOC, "https://example.com/a/b").unwrap();
+        assert_eq!(res["title"], vec!["Example Title"]);
+        assert!(res["site_name"].is_empty());
+    }
+
+    #[test]
+    fn accumulate_and_unique() {
+        let s = spec(r#"{ "url": "", "selectors": [
+            { "name": "headings", "selector": "h2", "unique": true },
+            { "name": "headings", "selector": "h3" },
+            { "name": "links", "selector": "a[href]", "output": { "type": "attr", "attr": "href", "absolute": true }, "unique": true }
+        ]}"#);
+        let res = evaluate(&s, DOC, "https://example.com/a/b").unwrap();
+        assert_eq!(res["headings"], vec!["A", "B", "C"]);
+        assert_eq!(res["links"], vec!["https://example.com/x", "https://other.example/y"]);
+    }
+
+    #[test]
+    fn invalid_selector() {
+        let s = spec(r#"{ "url": "", "selectors": [ { "name": "bad", "selector": "a[" } ]}"#);
+        assert_eq!(s.validate().unwrap_err().code, ErrorCode::InvalidSelector);
+        assert!(evaluate(&s, DOC, "https://example.com/").is_err());
+    }
+
+    #[test]
+    fn invalid_specs() {
+        let unnamed = spec(r#"{ "url": "", "selectors": [ { "name": "", "selector": "a" } ]}"#);
+        assert_eq!(unnamed.validate().unwrap_err().code, ErrorCode::InvalidRequest);
+        let no_attr = spec(r#"{ "url": "", "selectors": [ { "name": "links", "selector": "a", "output": { "type": "attr", "attr": "" } } ]}"#);
+        assert_eq!(no_attr.validate().unwrap_err().code, ErrorCode::InvalidRequest);
+        let static_actions = spec(r#"{ "url": "", "render": { "enabled": false }, "actions": [{ "type": "click", "selector": "a" }] }"#);
+        assert_eq!(static_actions.validate().unwrap_err().code, ErrorCode::InvalidRequest);
+    }
+
+    #[test]
+    fn sample_spec_parses() {
+        let s: ScrapeSpec = serde_json::from_str(include_str!("../spec.sample.json")).unwrap();
+        s.validate().unwrap();
+        let res = evaluate(&s, DOC, "https://example.com/").unwrap();
+        assert_eq!(res["lang"], vec!["ja"]);
+        assert_eq!(res["favicon"], vec!["https://example.com/favicon.ico"]);
+    }
+}
@@ -81,17 +81,260 @@ fn from_hex(b: u8) -> Option<u8> {
 	}
 }
 
-pub fn url_normalize(base_url: &str, href: &str) -> String {
-    if href.starts_with("http://") || href.starts_with("https://") {
-        href.to_string()
-    } else {
-        let base = base_url.trim_end_matches('/');
-        if href.starts_with('/') {
-            format!("{}/{}", base, href.trim_start_matches('/'))
-        } else {
-            format!("{}/{}", base, href)
-        }
-    }
+/// Components of a URI reference (RFC 3986, appendix B)
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct UrlParts<'a> {
+	scheme: Option<&'a str>,
+	authority: Option<&'a str>,
+	path: &'a str,
+	query: Option<&'a str>,
+	fragment: Option<&'a str>,
+}
+
+fn split_url(input: &str) -> UrlParts<'_> {
+	let (rest, fragment) = match input.split_once('#') {
+		Some((r, f)) => (r, Some(f)),
+		None => (input, None),
+	};
+	let (rest, query) = match rest.split_once('?') {
+		Some((r, q)) => (r, Some(q)),
+		None => (rest, None),
+	};
+	// scheme: ALPHA *( ALPHA / DIGIT / "+" / "-" / "." ) before the first ':' and any '/'
+	let (scheme, rest) = match rest.find(':') {
+		Some(i) if i > 0
+			&& !rest[..i].contains('/')
+			&& rest.as_bytes()[0].is_ascii_alphabetic()
+			&& rest[..i].bytes().all(|b| b.is_ascii_alphanumeric() || b == b'+' || b == b'-' || b == b'.') =>
+		{
+			(Some(&rest[..i]), &rest[i + 1..])
+		}
+		_ => (None, rest),
+	};
+	let (authority, path) = match rest.strip_prefix("//") {
+		Some(r) => match r.find('/') {
+			Some(i) => (Some(&r[..i]), &r[i..]),
+			None => (Some(r), ""),
+		},
+		None => (None, rest),
+	};
+	UrlParts { scheme, authority, path, query, fragment }
+}
+
+fn join_url(scheme: Option<&str>, authority: Option<&str>, path: &str, query: Option<&str>, fragment: Option<&str>) -> String {
+	let mut out = String::new();
+	if let Some(scheme) = scheme {
+		out.push_str(scheme);
+		out.push(':');
+	}
+	if let Some(authority) = authority {
+		out.push_str("//");
+		out.push_str(authority);
+	}
+	out.push_str(path);
+	if let Some(query) = query {
+		out.push('?');
+		out.push_str(query);
+	}
+	if let Some(fragment) = fragment {
+		out.push('#');
+		out.push_str(fragment);
+	}
+	out
+}
+
+/// Remove `.` and `..` segments from a path (RFC 3986, 5.2.4).
+pub fn remove_dot_segments(path: &str) -> String {
+	let mut input = path;
+	let mut output: Vec<&str> = Vec::new();
+	while !input.is_empty() {
+		if let Some(rest) = input.strip_prefix("../") {
+			input = rest;
+		} else if let Some(rest) = input.strip_prefix("./") {
+			input = rest;
+		} else if input.starts_with("/./") {
+			input = &input[2..];
+		} else if input == "/." {
+			input = "/";
+		} else if input.starts_with("/../") || input == "/.." {
+			input = if input == "/.." { "/" } else { &input[3..] };
+			output.pop();
+		} else if input == "." || input == ".." {
+			input = "";
+		} else {
+			// move the first segment (with its leading '/') to the output
+			let start = if input.starts_with('/') { 1 } else { 0 };
+			let end = input[start..].find('/').map(|i| i + start).unwrap_or(input.len());
+			output.push(&input[..end]);
+			input = &input[end..];
+		}
+	}
+	output.concat()
+}
+
+/// Resolve a URI reference against a base URI (RFC 3986, 5.2.2).
+pub fn url_resolve(base: &str, reference: &str) -> String {
+	let base = split_url(base);
+	let r = split_url(reference);
+
+	if r.scheme.is_some() {
+		return join_url(r.scheme, r.authority, &remove_dot_segments(r.path), r.query, r.fragment);
+	}
+	if r.authority.is_some() {
+		return join_url(base.scheme, r.authority, &remove_dot_segments(r.path), r.query, r.fragment);
+	}
+	let (path, query) = if r.path.is_empty() {
+		(base.path.to_string(), r.query.or(base.query))
+	} else if r.path.starts_with('/') {
+		(remove_dot_segments(r.path), r.query)
+	} else {
+		// merge (5.2.3)
+		let merged = if base.authority.is_some() && base.path.is_empty() {
+			format!("/{}", r.path)
+		} else {
+			match base.path.rfind('/') {
+				Some(i) => format!("{}{}", &base.path[..=i], r.path),
+				None => r.path.to_string(),
+			}
+		};
+		(remove_dot_segments(&merged), r.query)
+	};
+	join_url(base.scheme, base.authority, &path, query, r.fragment)
+}
+
+/// Query parameters that only carry tracking information
+const TRACKING_PARAMS: &[&str] = &[
+	"gclid", "dclid", "fbclid", "msclkid", "yclid", "igshid", "mc_cid", "mc_eid", "_ga", "_gl", "ref_src",
+];
+
+fn is_tracking_param(param: &str) -> bool {
+	let name = param.split('=').next().unwrap_or("").to_ascii_lowercase();
+	name.starts_with("utm_") || TRACKING_PARAMS.contains(&name.as_str())
+}
+
+/// Canonical form of an absolute http(s) URL.
+///
+/// Lowercases scheme and host, drops the default port and the fragment, removes
+/// dot segments and uses `/` for an empty path. With `strip_tracking`, `utm_*` and
+/// other click-tracking query parameters are removed too.
+/// Returns `None` for anything that is not http(s) (`mailto:`, `javascript:`, `tel:`, ...).
+pub fn url_canonicalize(url: &str, strip_tracking: bool) -> Option<String> {
+	let parts = split_url(url.trim());
+	let scheme = parts.scheme?.to_ascii_lowercase();
+	if scheme != "http" && scheme != "https" {
+		return None;
+	}
+	let authority = parts.authority?;
+	let (userinfo, host_port) = match authority.rsplit_once('@') {
+		Some((u, h)) => (Some(u), h),
+		None => (None, authority),
+	};
+	let (host, port) = match host_port.rfind(':') {
+		Some(i) if !host_port[i..].contains(']') => (&host_port[..i], Some(&host_port[i + 1..])),
+		_ => (host_port, None),
+	};
+	if host.is_empty() {
+		return None;
+	}
+	let default_port = if scheme == "http" { "80" } else { "443" };
+	let mut authority = String::new();
+	if let Some(userinfo) = userinfo {
+		authority.push_str(userinfo);
+		authority.push('@');
+	}
+	authority.push_str(&host.to_ascii_lowercase());
+	if let Some(port) = port.filter(|p| !p.is_empty() && *p != default_port) {
+		authority.push(':');
+		authority.push_str(port);
+	}
+
+	let path = remove_dot_segments(parts.path);
+	let path = if path.is_empty() { "/".to_string() } else { path };
+
+	let query = match parts.query {
+		Some(q) if strip_tracking => {
+			let kept: Vec<&str> = q.split('&').filter(|p| !p.is_empty() && !is_tracking_param(p)).collect();
+			if kept.is_empty() { None } else { Some(kept.join("&")) }
+		}
+		Some(q) => Some(q.to_string()),
+		None => None,
+	};
+
+	Some(join_url(Some(&scheme), Some(&authority), &path, query.as_deref(), None))
+}
+
+/// Resolve a link found in a document and canonicalize it.
+///
+/// `base` is the document base (the page URL, or `<base href>` when present).
+/// Surrounding whitespace and embedded tabs/newlines in `href` are ignored, as browsers do.
+/// Returns `None` for links that do not lead to an http(s) page.
+pub fn resolve_link(base: &str, href: &str) -> Option<String> {
+	let href: String = href.trim().chars().filter(|c| !matches!(c, '\t' | '\n' | '\r')).collect();
+	if href.is_empty() {
+		return None;
+	}
+	url_canonicalize(&url_resolve(base, &href), false)
+}
+
+/// Host part of an absolute URL, lowercased and without userinfo or port.
+pub fn url_host(url: &str) -> Option<String> {
+	let rest = url.split_once("://")?.1;
+	let authority = rest.split(['/', '?', '#']).next()?;
+	let host_port = authority.rsplit_once('@').map(|(_, h)| h).unwrap_or(authority);
+	let host = if host_port.starts_with('[') {
+		// IPv6 literal
+		host_port.split_once(']').map(|(h, _)| &h[1..])?
+	} else {
+		host_port.split(':').next()?
+	};
+	if host.is_empty() { None } else { Some(host.to_ascii_lowercase()) }
+}
+
+/// Path part of an absolute URL (`/` when empty).
+pub fn url_path(url: &str) -> &str {
+	let rest = match url.split_once("://") {
+		Some((_, rest)) => rest,
+		None => return "/",
+	};
+	let rest = rest.split(['?', '#']).next().unwrap_or("");
+	match rest.find('/') {
+		Some(i) => &rest[i..],
+		None => "/",
+	}
+}
+
+/// Second-level labels under which registrations happen one level deeper
+/// (e.g. `example.co.jp`). Not a full public suffix list.
+const MULTI_LABEL_SUFFIXES: &[&str] = &[
+	"co.jp", "ne.jp", "or.jp", "ac.jp", "go.jp", "ed.jp", "gr.jp", "lg.jp",
+	"co.uk", "org.uk", "ac.uk", "gov.uk", "me.uk", "ltd.uk", "plc.uk",
+	"com.au", "net.au", "org.au", "edu.au", "gov.au",
+	"com.cn", "net.cn", "org.cn", "gov.cn", "edu.cn",
+	"com.tw", "org.tw", "edu.tw", "gov.tw",
+	"co.kr", "or.kr", "go.kr", "ac.kr",
+	"com.br", "net.br", "org.br", "gov.br",
+	"co.nz", "org.nz", "ac.nz", "govt.nz",
+	"co.in", "net.in", "org.in", "ac.in", "gov.in",
+	"com.hk", "org.hk", "edu.hk", "gov.hk",
+	"com.sg", "edu.sg", "gov.sg", "co.za", "com.mx", "com.ar", "com.tr",
+];
+
+/// Approximate registrable domain ("eTLD+1") of a host.
+///
+/// `www.example.co.jp` -> `example.co.jp`, `blog.example.com` -> `example.com`.
+/// IP addresses and single-label hosts are returned as is.
+pub fn registrable_domain(host: &str) -> String {
+	let host = host.trim_end_matches('.').to_ascii_lowercase();
+	if host.parse::<std::net::IpAddr>().is_ok() {
+		return host;
+	}
+	let labels: Vec<&str> = host.split('.').collect();
+	if labels.len() <= 2 {
+		return host;
+	}
+	let suffix2 = labels[labels.len() - 2..].join(".");
+	let take = if MULTI_LABEL_SUFFIXES.contains(&suffix2.as_str()) { 3 } else { 2 };
+	labels[labels.len() - take.min(labels.len())..].join(".")
 }
 
 #[cfg(test)]
@@ -121,5 +364,66 @@ mod tests {
 		// malformed percent -> leaves '%' when strict fails
 		assert_eq!(url_decode("%ZZ"), "%ZZ");
 	}
-}
 
+	#[test]
+	fn host_and_path() {
+		assert_eq!(url_host("https://User@WWW.Example.com:8080/a?b#c").as_deref(), Some("www.example.com"));
+		assert_eq!(url_host("http://[::1]:3000/").as_deref(), Some("::1"));
+		assert_eq!(url_host("mailto:foo@example.com"), None);
+		assert_eq!(url_path("https://example.com"), "/");
+		assert_eq!(url_path("https://example.com/a/b?c=/d"), "/a/b");
+	}
+
+	#[test]
+	fn registrable() {
+		assert_eq!(registrable_domain("blog.example.com"), "example.com");
+		assert_eq!(registrable_domain("www.example.co.jp"), "example.co.jp");
+		assert_eq!(registrable_domain("example.co.jp"), "example.co.jp");
+		assert_eq!(registrable_domain("localhost"), "localhost");
+		assert_eq!(registrable_domain("192.168.0.1"), "192.168.0.1");
+	}
+
+	#[test]
+	fn resolve_rfc3986_examples() {
+		let base = "http://a/b/c/d;p?q";
+		let cases = [
+			("g:h", "g:h"), ("g", "http://a/b/c/g"), ("./g", "http://a/b/c/g"), ("g/", "http://a/b/c/g/"),
+			("/g", "http://a/g"), ("//g", "http://g"), ("?y", "http://a/b/c/d;p?y"), ("g?y", "http://a/b/c/g?y"),
+			("#s", "http://a/b/c/d;p?q#s"), ("g#s", "http://a/b/c/g#s"), (";x", "http://a/b/c/;x"),
+			("", "http://a/b/c/d;p?q"), (".", "http://a/b/c/"), ("./", "http://a/b/c/"), ("..", "http://a/b/"),
+			("../", "http://a/b/"), ("../g", "http://a/b/g"), ("../..", "http://a/"), ("../../g", "http://a/g"),
+			("../../../g", "http://a/g"), ("/./g", "http://a/g"), ("/../g", "http://a/g"), ("g.", "http://a/b/c/g."),
+			(".g", "http://a/b/c/.g"), ("g..", "http://a/b/c/g.."), ("./../g", "http://a/b/g"), ("./g/.", "http://a/b/c/g/"),
+			("g/./h", "http://a/b/c/g/h"), ("g/../h", "http://a/b/c/h"), ("g;x=1/./y", "http://a/b/c/g;x=1/y"),
+		];
+		for (reference, expected) in cases {
+			assert_eq!(url_resolve(base, reference), expected, "reference {:?}", reference);
+		}
+	}
+
+	#[test]
+	fn canonicalize() {
+		assert_eq!(url_canonicalize("HTTPS://Example.COM:443/a/./b/../c#frag", false).as_deref(), Some("https://example.com/a/c"));
+		assert_eq!(url_canonicalize("http://example.com:8080", false).as_deref(), Some("http://example.com:8080/"));
+		assert_eq!(url_canonicalize("http://[::1]:80/x", false).as_deref(), Some("http://[::1]/x"));
+		assert_eq!(
+			url_canonicalize("https://example.com/?utm_source=x&id=1&fbclid=y", true).as_deref(),
+			Some("https://example.com/?id=1")
+		);
+		assert_eq!(url_canonicalize("https://example.com/?utm_source=x", true).as_deref(), Some("https://example.com/"));
+		assert_eq!(url_canonicalize("mailto:a@example.com", false), None);
+	}
+
+	#[test]
+	fn resolve_links() {
+		let base = "https://example.com/docs/guide/index.html";
+		assert_eq!(resolve_link(base, "../api").as_deref(), Some("https://example.com/docs/api"));
+		assert_eq!(resolve_link(base, "//cdn.example.com/x.js").as_deref(), Some("https://cdn.example.com/x.js"));
+		assert_eq!(resolve_link(base, "?page=2").as_deref(), Some("https://example.com/docs/guide/index.html?page=2"));
+		assert_eq!(resolve_link(base, "#top").as_deref(), Some("https://example.com/docs/guide/index.html"));
+		assert_eq!(resolve_link(base, " next.html\n").as_deref(), Some("https://example.com/docs/guide/next.html"));
+		assert_eq!(resolve_link(base, "javascript:void(0)"), None);
+		assert_eq!(resolve_link(base, "tel:+81-0000"), None);
+		assert_eq!(resolve_link(base, "mailto:a@example.com"), None);
+	}
+}
@@ -0,0 +1,162 @@
+use std::pin::Pin;
+use std::time::Duration;
+
+use chromiumoxide::Page;
+use tokio::time::Instant;
+
+use super::error::{ErrorCode, ScrapeError};
+use super::network::NavigationRecorder;
+use super::schema::WaitCondition;
+
+/// How often the wait conditions are checked again
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Wait until every condition holds, or fail once `limit` has passed.
+///
+/// An unmet `selector` condition fails with `SelectorNotFound`, anything else with `WaitTimeout`.
+pub async fn wait_until(
+    page: &Page,
+    recorder: &NavigationRecorder,
+    conditions: &[WaitCondition],
+    limit: Duration,
+) -> Result<(), ScrapeError> {
+    if conditions.is_empty() {
+        return Ok(());
+    }
+    let deadline = Instant::now() + limit;
+    let mut idle = IdleTimers::default();
+    loop {
+        let mut probe = Probe { page, recorder, idle: &mut idle, next: 0, last_error: None };
+        // check every condition on each round so the network-idle timers stay current
+        let mut unmet = None;
+        for condition in conditions {
+            if !probe.check(condition).await && unmet.is_none() {
+                unmet = Some(condition);
+            }
+        }
+        let Some(condition) = unmet else {
+            return Ok(());
+        };
+        if Instant::now() >= deadline {
+            let code = match condition {
+                WaitCondition::Selector { .. } => ErrorCode::SelectorNotFound,
+                _ => ErrorCode::WaitTimeout,
+            };
+            let mut message = format!("wait condition '{}' not met after {}ms", condition, limit.as_millis());
+            if let Some(e) = probe.last_error {
+                message = format!("{}: {}", message, e);
+            }
+            return Err(ScrapeError::new(code, message));
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Since when each `network_idle` condition (in evaluation order) has been satisfied
+#[derive(Debug, Default)]
+struct IdleTimers {
+    since: Vec<Option<Instant>>,
+}
+
+impl IdleTimers {
+    /// Record the in-flight count seen by condition `index` and tell whether
+    /// it has stayed at or below `max_inflight` for `idle`.
+    fn update(&mut self, index: usize, inflight: usize, max_inflight: usize, idle: Duration, now: Instant) -> bool {
+        if self.since.len() <= index {
+            self.since.resize(index + 1, None);
+        }
+        let since = &mut self.since[index];
+        if inflight > max_inflight {
+            *since = None;
+            return false;
+        }
+        now.duration_since(*since.get_or_insert(now)) >= idle
+    }
+}
+
+/// One round of checks
+struct Probe<'a> {
+    page: &'a Page,
+    recorder: &'a NavigationRecorder,
+    idle: &'a mut IdleTimers,
+    /// index of the next `network_idle` condition
+    next: usize,
+    /// last script or CDP error, reported when the wait times out
+    last_error: Option<String>,
+}
+
+impl Probe<'_> {
+    fn check<'b>(&'b mut self, condition: &'b WaitCondition) -> Pin<Box<dyn Future<Output = bool> + Send + 'b>> {
+        Box::pin(async move {
+            match condition {
+                WaitCondition::DomContentLoaded => self.eval("document.readyState !== 'loading'".to_string()).await,
+                WaitCondition::Load => self.eval("document.readyState === 'complete'".to_string()).await,
+                WaitCondition::NetworkIdle { max_inflight, idle_ms } => {
+                    let index = self.next;
+                    self.next += 1;
+                    let inflight = self.recorder.inflight();
+                    self.idle.update(index, inflight, *max_inflight, Duration::from_millis(*idle_ms), Instant::now())
+                }
+                WaitCondition::Selector { selector } => match self.page.find_element(selector.as_str()).await {
+                    Ok(_) => true,
+                    Err(e) => {
+                        self.last_error = Some(e.to_string());
+                        false
+                    }
+                },
+                WaitCondition::Text { text } => {
+                    let text = serde_json::to_string(text).unwrap_or_default();
+                    self.eval(format!("(document.body ? document.body.innerText : '').includes({})", text)).await
+                }
+                WaitCondition::Js { expression } => {
+                    self.eval(format!("(async () => Boolean(await ({})))()", expression)).await
+                }
+                WaitCondition::Any { conditions } => {
+                    let mut met = false;
+                    for condition in conditions {
+                        met |= self.check(condition).await;
+                    }
+                    met
+                }
+            }
+        })
+    }
+
+    /// Evaluate a boolean expression; script errors count as "not yet".
+    async fn eval(&mut self, expression: String) -> bool {
+        let result = self.page.evaluate_expression(expression).await;
+        match result.map(|r| r.into_value::<bool>()) {
+            Ok(Ok(value)) => value,
+            Ok(Err(e)) => {
+                self.last_error = Some(e.to_string());
+                false
+            }
+            Err(e) => {
+                self.last_error = Some(e.to_string());
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn network_idle_timer() {
+        let mut timers = IdleTimers::default();
+        let start = Instant::now();
+        let idle = Duration::from_millis(500);
+        assert!(!timers.update(0, 0, 0, idle, start));
+        assert!(!timers.update(0, 0, 0, idle, start + Duration::from_millis(300)));
+        // a request starts: the quiet period begins again
+        assert!(!timers.update(0, 1, 0, idle, start + Duration::from_millis(400)));
+        assert!(!timers.update(0, 0, 0, idle, start + Duration::from_millis(600)));
+        assert!(timers.update(0, 0, 0, idle, start + Duration::from_millis(1100)));
+
+        // a second condition tolerating two long-polling requests has its own timer
+        assert!(!timers.update(1, 2, 2, idle, start));
+        assert!(timers.update(1, 1, 2, idle, start + Duration::from_millis(500)));
+    }
+}
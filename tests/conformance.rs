@@ -0,0 +1,330 @@
+//! Conformance suite shared by the `Client` backends: every API must behave the same
+//! whether it runs on the embedded engine or goes through the HTTP server.
+//!
+//! Each run serves a small fixture site on 127.0.0.1 and drives it through `Client`:
+//!
+//! - `cargo test --features standalone --test conformance -- --ignored` launches the engine,
+//!   serves the HTTP API from it on an ephemeral port and runs every check against both
+//!   `Client::new(url)` and the embedded client (needs Chromium)
+//! - `CRAWLER_API_URL=http://localhost:3773 cargo test --test conformance -- --ignored` runs the
+//!   checks against an API server already running on the same machine
+//!
+//! Both are `#[ignore]`d since they need a browser; once asked for, a missing backend fails the test.
+
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use wk_371tti_net_crawler::error::ErrorCode;
+use wk_371tti_net_crawler::schema::{CookieFormat, CrawlRequest, ExtractRequest, JobRequest, JobStatus, PdfRequest, ScrapeRequest, ScrapeSpec, ScraperResult};
+use wk_371tti_net_crawler::{CaptureAPIBuilder, Client, ScraperAPIBuilder};
+
+const INDEX: &str = r#"<!doctype html>
+<html lang="en">
+<head>
+<title>Fixture</title>
+<style>
+  body { margin: 0; }
+  .card { width: 200px; height: 100px; margin: 10px; background: #36c; }
+  #hidden { display: none; }
+  #empty { width: 0; height: 0; }
+  #below { margin-top: 3000px; width: 300px; height: 150px; background: #c63; }
+</style>
+</head>
+<body>
+<h1>Fixture</h1>
+<div class="card">one</div>
+<div class="card">two</div>
+<div class="card">three</div>
+<a href="/next?x=1&y=2">next</a>
+<div id="hidden">hidden</div>
+<div id="empty"></div>
+<div id="below">below the fold</div>
+</body>
+</html>"#;
+
+const NEXT: &str = r#"<!doctype html>
+<html lang="en"><head><title>Next</title></head><body><h1>Next</h1></body></html>"#;
+
+/// Serve the fixture pages on a random local port and return the base URL
+async fn fixture() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut buf = vec![0u8; 8192];
+                let n = stream.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]);
+                let path = request.split_whitespace().nth(1).unwrap_or("/");
+                let path = path.split('?').next().unwrap_or(path);
+                if path == "/slow" {
+                    // long enough for a job to be cancelled while it loads
+                    tokio::time::sleep(Duration::from_secs(30)).await;
+                }
+                let (status, body) = match path {
+                    "/" => ("200 OK", INDEX),
+                    "/next" | "/slow" => ("200 OK", NEXT),
+                    _ => ("404 Not Found", ""),
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body,
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            });
+        }
+    });
+    base
+}
+
+/// Width and height from a PNG header
+fn png_size(data: &[u8]) -> (u32, u32) {
+    assert_eq!(&data[..8], b"\x89PNG\r\n\x1a\n", "not a PNG");
+    let width = u32::from_be_bytes(data[16..20].try_into().unwrap());
+    let height = u32::from_be_bytes(data[20..24].try_into().unwrap());
+    (width, height)
+}
+
+#[cfg(feature = "standalone")]
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "needs Chromium"]
+async fn conformance() {
+    use std::sync::Arc;
+
+    use wk_371tti_net_crawler::backend::EmbeddedBackend;
+    use wk_371tti_net_crawler::server;
+
+    let backend = EmbeddedBackend::new().await.expect("failed to launch the embedded engine");
+    // the API server runs in this process, on the same engine
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let server = server::routes(&backend.engine, backend.jobs.clone())
+        .server()
+        .host([127, 0, 0, 1])
+        .port(port)
+        .build();
+    let remote = Client::new(&format!("http://127.0.0.1:{}", port));
+    let embedded = Client::with_backend(Arc::new(backend));
+    let base = fixture().await;
+
+    tokio::select! {
+        _ = server.run_async() => panic!("API server stopped"),
+        _ = async {
+            wait_for_server(port).await;
+            for client in [&remote, &embedded] {
+                check(client, &base).await;
+            }
+        } => {}
+    }
+}
+
+#[cfg(not(feature = "standalone"))]
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "needs a running API server"]
+async fn conformance() {
+    let url = std::env::var("CRAWLER_API_URL").expect("set CRAWLER_API_URL to the API server, e.g. http://localhost:3773");
+    check(&Client::new(&url), &fixture().await).await;
+}
+
+/// Wait until the in-process API server accepts connections
+#[cfg(feature = "standalone")]
+async fn wait_for_server(port: u16) {
+    for _ in 0..100 {
+        if tokio::net::TcpStream::connect(("127.0.0.1", port)).await.is_ok() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("API server did not start on port {}", port);
+}
+
+/// Every check, against one client
+async fn check(client: &Client, base: &str) {
+    eprintln!("conformance: {} backend", client.backend().name());
+    capture(client, base).await;
+    capture_errors(client, base).await;
+    capture_elements(client, base).await;
+    scrape(client, base).await;
+    scrape_spec(client, base).await;
+    extract(client, base).await;
+    crawl(client, base).await;
+    pdf(client, base).await;
+    jobs(client, base).await;
+    invalid_requests(client, base).await;
+    sessions(client).await;
+}
+
+fn spec(value: serde_json::Value) -> ScrapeSpec {
+    serde_json::from_value(value).unwrap()
+}
+
+fn crawl_request(value: serde_json::Value) -> CrawlRequest {
+    serde_json::from_value(value).unwrap()
+}
+
+async fn capture(client: &Client, base: &str) {
+    // no selector: the whole page, beyond the first screen
+    let page = client.capture_api(CaptureAPIBuilder::new(base).build()).await.unwrap();
+    let (_, height) = png_size(&page);
+    assert!(height >= 3000, "full-page capture is only {}px high", height);
+
+    // elements keep their 2:1 shape, including one far below the fold
+    for selector in [".card", "#below"] {
+        let image = client.capture_api(CaptureAPIBuilder::new(base).set_selector(selector).build()).await.unwrap();
+        let (width, height) = png_size(&image);
+        assert_eq!(width, height * 2, "{} captured as {}x{}", selector, width, height);
+    }
+}
+
+async fn capture_errors(client: &Client, base: &str) {
+    for selector in ["#hidden", "#empty"] {
+        let e = client.capture_api(CaptureAPIBuilder::new(base).set_selector(selector).build()).await.unwrap_err();
+        assert_eq!(e.code(), ErrorCode::ElementNotVisible, "{}: {}", selector, e);
+    }
+    let missing = CaptureAPIBuilder::new(base)
+        .set_selector("#missing")
+        .set_selector_timeout(Duration::from_millis(500))
+        .build();
+    assert_eq!(client.capture_api(missing).await.unwrap_err().code(), ErrorCode::SelectorNotFound);
+}
+
+async fn capture_elements(client: &Client, base: &str) {
+    let api = CaptureAPIBuilder::new(base).set_selector(".card").set_all(true).add_selector("#hidden").build();
+    let captures = client.capture_elements(api).await.unwrap();
+    let manifest = &captures.manifest;
+    assert_eq!(manifest.elements.len(), 3);
+    assert_eq!(manifest.elements.iter().map(|e| e.match_index).collect::<Vec<_>>(), vec![0, 1, 2]);
+    assert_eq!(manifest.empty.len(), 1);
+    assert_eq!(manifest.empty[0].selector, "#hidden");
+    for (info, image) in manifest.elements.iter().zip(&captures.images) {
+        assert_eq!(info.file, format!("element-{}.png", info.index));
+        png_size(image);
+    }
+}
+
+async fn scrape(client: &Client, base: &str) {
+    // the query of the target URL must reach the browser untouched
+    let url = format!("{}/next?x=1&y=2", base);
+    let api = ScraperAPIBuilder::new(&url).add_selector("h1").add_selector("a[href*='&y']").build();
+    match client.scraper(api).await.unwrap() {
+        ScraperResult::Success { status, url: reported, results } => {
+            assert_eq!(status, 200);
+            assert_eq!(reported, url);
+            assert_eq!(results.title.as_deref(), Some("Next"));
+            assert_eq!(results.contents["h1"], vec!["Next".to_string()]);
+            assert!(results.contents["a[href*='&y']"].is_empty());
+        }
+        ScraperResult::Failed { error, code } => panic!("scrape failed ({}): {}", code, error),
+    }
+}
+
+async fn scrape_spec(client: &Client, base: &str) {
+    let spec = spec(serde_json::json!({
+        "url": base,
+        "timeout_ms": 30000,
+        "selectors": [
+            { "name": "title", "selector": "h1", "first": true },
+            { "name": "cards", "selector": ".card" },
+            { "name": "links", "selector": "a[href]", "output": { "type": "attr", "attr": "href", "absolute": true } }
+        ]
+    }));
+    let results = client.scrape_spec(&spec).await.unwrap();
+    assert_eq!(results.status, 200);
+    assert_eq!(results.results["title"], vec!["Fixture"]);
+    assert_eq!(results.results["cards"], vec!["one", "two", "three"]);
+    assert_eq!(results.results["links"], vec![format!("{}/next?x=1&y=2", base)]);
+}
+
+async fn extract(client: &Client, base: &str) {
+    let req = ExtractRequest { url: base.to_string(), document: INDEX.to_string(), selectors: vec![".card".to_string()], text_selector: None };
+    let results = client.extract(&req).await.unwrap();
+    assert_eq!(results.title.as_deref(), Some("Fixture"));
+    assert_eq!(results.contents[".card"], vec!["one", "two", "three"]);
+    assert_eq!(results.links, vec![format!("{}/next?x=1&y=2", base)]);
+}
+
+async fn crawl(client: &Client, base: &str) {
+    let req = crawl_request(serde_json::json!({ "seeds": [base], "max_depth": 1, "selectors": ["h1"] }));
+    let mut pages = client.crawl(&req).await.unwrap().pages;
+    pages.sort_by_key(|page| page.depth);
+    assert_eq!(pages.iter().map(|page| page.depth).collect::<Vec<_>>(), vec![0, 1]);
+    assert_eq!(pages[1].url, format!("{}/next?x=1&y=2", base));
+    for (page, title) in pages.iter().zip(["Fixture", "Next"]) {
+        match &page.result {
+            ScraperResult::Success { results, .. } => assert_eq!(results.contents["h1"], vec![title.to_string()]),
+            ScraperResult::Failed { error, code } => panic!("{} failed ({}): {}", page.url, code, error),
+        }
+    }
+}
+
+async fn pdf(client: &Client, base: &str) {
+    let pdf = client.pdf(&PdfRequest::new(base)).await.unwrap();
+    assert!(pdf.starts_with(b"%PDF"));
+}
+
+async fn jobs(client: &Client, base: &str) {
+    let scrape = JobRequest::Scrape(ScrapeRequest {
+        url: format!("{}/next", base),
+        selectors: vec!["h1".to_string()],
+        text_selector: None,
+        waiting_selector: None,
+        render: Default::default(),
+        options: Default::default(),
+    });
+    let info = client.run_job(&scrape, Duration::from_millis(200)).await.unwrap();
+    assert_eq!(info.status, JobStatus::Succeeded, "{:?}", info.error);
+    match serde_json::from_value::<ScraperResult>(info.result.unwrap()).unwrap() {
+        ScraperResult::Success { results, .. } => assert_eq!(results.contents["h1"], vec!["Next".to_string()]),
+        ScraperResult::Failed { error, code } => panic!("scrape job failed ({}): {}", code, error),
+    }
+
+    let info = client.run_job(&JobRequest::Spec(spec(serde_json::json!({
+        "url": base,
+        "selectors": [{ "name": "title", "selector": "h1" }]
+    }))), Duration::from_millis(200)).await.unwrap();
+    assert_eq!(info.status, JobStatus::Succeeded, "{:?}", info.error);
+    assert_eq!(info.result.unwrap()["results"]["title"], serde_json::json!(["Fixture"]));
+
+    // cancelled while the page is still loading
+    let slow = JobRequest::Crawl(crawl_request(serde_json::json!({ "seeds": [format!("{}/slow", base)], "max_depth": 0 })));
+    let info = client.submit_job(&slow).await.unwrap();
+    assert!(!info.status.is_finished());
+    assert_eq!(client.cancel_job(&info.id).await.unwrap().status, JobStatus::Cancelled);
+    assert_eq!(client.job(&info.id).await.unwrap().status, JobStatus::Cancelled);
+}
+
+async fn invalid_requests(client: &Client, base: &str) {
+    let bad_selector = spec(serde_json::json!({ "url": base, "selectors": [{ "name": "bad", "selector": "a[" }] }));
+    assert_eq!(client.scrape_spec(&bad_selector).await.unwrap_err().code(), ErrorCode::InvalidSelector);
+
+    let static_actions = spec(serde_json::json!({
+        "url": base,
+        "render": { "enabled": false },
+        "actions": [{ "type": "click", "selector": "a" }]
+    }));
+    assert_eq!(client.scrape_spec(&static_actions).await.unwrap_err().code(), ErrorCode::InvalidRequest);
+
+    let info = client.run_job(&JobRequest::Spec(bad_selector), Duration::from_millis(200)).await.unwrap();
+    assert_eq!((info.status, info.error_code), (JobStatus::Failed, Some(ErrorCode::InvalidSelector)));
+
+    let bad_scope = crawl_request(serde_json::json!({ "seeds": [base], "scope": { "include": ["("] } }));
+    assert_eq!(client.crawl(&bad_scope).await.unwrap_err().code(), ErrorCode::InvalidRequest);
+    let bad_seed = crawl_request(serde_json::json!({ "seeds": ["not a url"] }));
+    assert_eq!(client.crawl(&bad_seed).await.unwrap_err().code(), ErrorCode::InvalidRequest);
+    let too_many = crawl_request(serde_json::json!({ "seeds": [base], "max_pages": 100_000 }));
+    assert_eq!(client.crawl(&too_many).await.unwrap_err().code(), ErrorCode::InvalidRequest);
+    assert_eq!(client.submit_job(&JobRequest::Crawl(too_many)).await.unwrap_err().code(), ErrorCode::InvalidRequest);
+}
+
+async fn sessions(client: &Client) {
+    let id = format!("conformance-{}", std::process::id());
+    let info = client.import_cookies(&id, "127.0.0.1\tFALSE\t/\tFALSE\t0\tflavor\tchocolate\n", CookieFormat::Netscape).await.unwrap();
+    assert_eq!(info.cookies, 1);
+    let exported = client.export_cookies(&id, CookieFormat::Json).await.unwrap();
+    assert!(exported.contains("chocolate"));
+    assert_eq!(client.delete_session(&id).await.unwrap().id, id);
+    assert_eq!(client.session(&id).await.unwrap_err().code(), ErrorCode::NotFound);
+    assert_eq!(client.job("no-such-job").await.unwrap_err().code(), ErrorCode::NotFound);
+}